@@ -0,0 +1,13 @@
+#![no_main]
+
+// any input gives a tree or a syntax error, a panic or crash is a bug
+use graphing_calculator::{parse, parse_expression};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+  let Ok(input) = std::str::from_utf8(data) else {
+    return;
+  };
+  let _ = parse(input);
+  let _ = parse_expression(input);
+});
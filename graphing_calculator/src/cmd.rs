@@ -1,25 +1,157 @@
-pub fn run(
-  args: Vec<String>,
-  commands: Vec<String>,
-  functions: Vec<&dyn Fn(Vec<String>) -> ()>,
-) -> () {
-  let mut args = args;
-  args.remove(0);
-  if args.len() == 0 {
-    println!(
-      "Usage:
-    draw <in file (.graph)> <out file (.svg)> <title>
-    solve <equation>
-    simultaneous <equation 1> <equation 2>"
-    );
-    return;
-  } else {
-    match commands.iter().position(|x| *x == args[0]) {
-      Some(i) => {
-        args.remove(0);
-        functions[i](args)
-      }
-      None => println!("Command not found."),
+use clap::{Parser, Subcommand, ValueEnum};
+use graphing_calculator::{Figure, Format, Theme};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(
+  version,
+  about = "Solve, expand and draw equations.",
+  after_help = "Exit codes:\n  0  success\n  1  the input could not be read, parsed or drawn, or a root failed --verify\n  2  the command line was invalid"
+)]
+pub struct Cli {
+  /// Only print results, not the original/expanded/solved working
+  #[arg(short, long, global = true)]
+  pub quiet: bool,
+
+  /// How results are written
+  #[arg(long, global = true, value_enum, default_value = "text")]
+  pub format: OutputFormat,
+
+  #[command(subcommand)]
+  pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+  /// Draw the @draw statements of a .graph file
+  Draw {
+    /// The .graph file, or a .json document written by parse, to read
+    input: PathBuf,
+    /// The image to write
+    output: PathBuf,
+    /// The title of the chart, the input file name by default
+    title: Option<String>,
+    #[command(flatten)]
+    figure: FigureArgs,
+  },
+  /// Parse a .graph file into a json document of its syntax trees
+  Parse {
+    /// The .graph file to read
+    input: PathBuf,
+    /// The .json file to write, stdout by default
+    output: Option<PathBuf>,
+  },
+  /// Solve an equation for a variable
+  Solve {
+    equation: String,
+    #[arg(default_value = "x")]
+    var: String,
+    /// Show each rule applied on the way to the solution
+    #[arg(long)]
+    steps: bool,
+    /// Put each root back into the equation and fail if it does not hold
+    #[arg(long)]
+    verify: bool,
+  },
+  /// Solve two or more equations together
+  Simultaneous {
+    #[arg(required = true, num_args = 2..)]
+    equations: Vec<String>,
+  },
+  /// Start an interactive session
+  Repl {
+    #[command(flatten)]
+    figure: FigureArgs,
+  },
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+  /// The working and results as readable text
+  Text,
+  /// The working and results with expressions written as latex
+  Latex,
+  /// One json document per command, with every expression's syntax tree
+  Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ImageFormat {
+  Svg,
+  Png,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ThemeName {
+  /// A grey background with white points of interest
+  Dark,
+  /// A white background with black points of interest and darker curves
+  Light,
+}
+
+#[derive(clap::Args)]
+pub struct FigureArgs {
+  /// The visible x range
+  #[arg(long, value_name = "MIN..MAX", default_value = "-20..20", value_parser = range, allow_hyphen_values = true)]
+  pub x_range: (f64, f64),
+  /// The visible y range
+  #[arg(long, value_name = "MIN..MAX", default_value = "-20..20", value_parser = range, allow_hyphen_values = true)]
+  pub y_range: (f64, f64),
+  /// The image size in pixels
+  #[arg(long, value_name = "WIDTHxHEIGHT", default_value = "750x750", value_parser = size)]
+  pub size: (u32, u32),
+  /// The image format, guessed from the output file extension by default
+  #[arg(long, value_enum)]
+  pub image: Option<ImageFormat>,
+  /// The colours of the background, grid and points of interest
+  #[arg(long, value_enum, default_value = "dark")]
+  pub theme: ThemeName,
+}
+
+impl FigureArgs {
+  pub fn figure(&self, output: Option<&PathBuf>) -> Figure {
+    let png = output
+      .and_then(|o| o.extension())
+      .is_some_and(|e| e == "png");
+    Figure {
+      size: self.size,
+      x: self.x_range,
+      y: self.y_range,
+      format: match self.image {
+        Some(ImageFormat::Png) => Format::Png,
+        Some(ImageFormat::Svg) => Format::Svg,
+        None if png => Format::Png,
+        None => Format::Svg,
+      },
+      theme: match self.theme {
+        ThemeName::Dark => Theme::Dark,
+        ThemeName::Light => Theme::Light,
+      },
     }
   }
 }
+
+fn range(s: &str) -> Result<(f64, f64), String> {
+  let (min, max) = s.split_once("..").ok_or("expected MIN..MAX")?;
+  let min: f64 = min
+    .trim()
+    .parse()
+    .map_err(|_| format!("invalid number '{}'", min))?;
+  let max: f64 = max
+    .trim()
+    .parse()
+    .map_err(|_| format!("invalid number '{}'", max))?;
+  if min < max {
+    Ok((min, max))
+  } else {
+    Err("MIN must be less than MAX".to_string())
+  }
+}
+
+fn size(s: &str) -> Result<(u32, u32), String> {
+  let (width, height) = s.split_once('x').ok_or("expected WIDTHxHEIGHT")?;
+  match (width.parse(), height.parse()) {
+    (Ok(w), Ok(h)) if w > 0 && h > 0 => Ok((w, h)),
+    _ => Err(format!("invalid size '{}'", s)),
+  }
+}
@@ -1,20 +1,359 @@
-use super::eval;
-use crate::syntax::parser::AST;
+use super::{eval, vertical};
+use crate::syntax::parser::{Argument, Command, AST};
+use plotters::coord::Shift;
+use plotters::element::DashedPathElement;
 use plotters::prelude::*;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+  Svg,
+  Png,
+}
+
+// the colours of everything but the curves, for a dark or a light background
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Theme {
+  #[default]
+  Dark,
+  Light,
+}
+
+impl Theme {
+  // opaque, so a png has the same grey as an svg rather than being blended onto black
+  fn background(&self) -> RGBColor {
+    match self {
+      Theme::Dark => RGBColor(153, 153, 153),
+      Theme::Light => WHITE,
+    }
+  }
+
+  // the points of interest and their labels
+  fn foreground(&self) -> RGBColor {
+    match self {
+      Theme::Dark => WHITE,
+      Theme::Light => BLACK,
+    }
+  }
+
+  // curves are darker on a light background, where a pale yellow would hardly show
+  fn lightness(&self) -> f64 {
+    match self {
+      Theme::Dark => 0.5f64,
+      Theme::Light => 0.4f64,
+    }
+  }
+}
+
+// the image size, viewport, file format and theme of a drawing
+#[derive(Debug, Clone)]
+pub struct Figure {
+  pub size: (u32, u32),
+  pub x: (f64, f64),
+  pub y: (f64, f64),
+  pub format: Format,
+  pub theme: Theme,
+}
+
+impl Default for Figure {
+  fn default() -> Figure {
+    Figure {
+      size: (750, 750),
+      x: (-20f64, 20f64),
+      y: (-20f64, 20f64),
+      format: Format::Svg,
+      theme: Theme::default(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Dash {
+  #[default]
+  Solid,
+  Dashed,
+  Dotted,
+}
+
+// the widest line a statement can ask for, in pixels
+const MAX_WIDTH: u32 = 10;
+
+// how a statement asks to be drawn, where anything it leaves out is chosen by the plot
+#[derive(Debug, Clone, PartialEq)]
+pub struct Style {
+  // red, green and blue, otherwise the colour comes from the place of the statement in the plot
+  pub colour: Option<(u8, u8, u8)>,
+  pub dash: Dash,
+  pub width: u32,
+  // shown in the legend in place of the equation
+  pub label: Option<String>,
+}
+
+impl Default for Style {
+  fn default() -> Style {
+    Style {
+      colour: None,
+      dash: Dash::Solid,
+      width: 1,
+      label: None,
+    }
+  }
+}
+
+impl Style {
+  // the options of the commands of a statement that draw its curve, leaving out those of `@panel`
+  // which set the viewport of a panel and those of `@tangent` which draws lines of its own
+  pub fn options(commands: &[Command]) -> impl Iterator<Item = (&String, &Argument)> {
+    commands
+      .iter()
+      .filter(|c| ["draw", "intercepts", "turning", "fit"].contains(&c.name.as_str()))
+      .flat_map(|c| c.options.iter())
+  }
+
+  // the style given by the options of a statement, stopping at the first one that is invalid
+  pub fn from(commands: &[Command]) -> Result<Style, String> {
+    let mut style = Style::default();
+    for (name, value) in Style::options(commands) {
+      style.set(name, value)?;
+    }
+    Ok(style)
+  }
+
+  // set one `name=value` option, where `color` and `colour` are the same
+  pub fn set(&mut self, name: &str, value: &Argument) -> Result<(), String> {
+    let value = value
+      .word()
+      .ok_or(format!("{} expects a single word, number or text", name))?;
+    let value = value.as_str();
+    match name {
+      "color" | "colour" => {
+        self.colour =
+          Some(hex(value).ok_or(format!("{} is not a colour, expected #rrggbb", value))?)
+      }
+      "style" => {
+        self.dash = match value {
+          "solid" => Dash::Solid,
+          "dashed" => Dash::Dashed,
+          "dotted" => Dash::Dotted,
+          _ => {
+            return Err(format!(
+              "{} is not a line style, expected solid, dashed or dotted",
+              value
+            ))
+          }
+        }
+      }
+      "width" => {
+        self.width = match value.parse::<u32>() {
+          Ok(width) if (1..=MAX_WIDTH).contains(&width) => width,
+          _ => {
+            return Err(format!(
+              "{} is not a width, expected a whole number from 1 to {}",
+              value, MAX_WIDTH
+            ))
+          }
+        }
+      }
+      "label" => self.label = Some(value.to_string()),
+      _ => {
+        return Err(format!(
+          "{} is not an option, expected color, style, width or label",
+          name
+        ))
+      }
+    }
+    Ok(())
+  }
+
+  // the length of each dash and the gap after it, in pixels
+  fn dashes(&self) -> (u32, u32) {
+    match self.dash {
+      Dash::Solid => (20, 0),
+      Dash::Dashed => (6 + 2 * self.width, 4 + 2 * self.width),
+      Dash::Dotted => (self.width, 3 * self.width + 1),
+    }
+  }
+}
+
+// `#rgb` or `#rrggbb` as red, green and blue
+fn hex(colour: &str) -> Option<(u8, u8, u8)> {
+  let digits = colour.strip_prefix('#')?;
+  let digits = match digits.len() {
+    3 => digits.chars().flat_map(|c| [c, c]).collect(),
+    6 => digits.to_string(),
+    _ => return None,
+  };
+  let channel = |i: usize| u8::from_str_radix(digits.get(i..i + 2)?, 16).ok();
+  Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+// one chart of a figure, with its own viewport and legend
+#[derive(Debug, Clone)]
+pub struct Panel {
+  // the caption of the chart, otherwise the title of the figure when it is the only panel
+  pub title: Option<String>,
+  pub x: (f64, f64),
+  pub y: (f64, f64),
+  pub curves: Vec<(AST, String, Style)>,
+  pub points: Vec<(f64, f64)>,
+}
+
 pub fn draw(
   file: &String,
-  statement: Vec<(AST, String)>,
+  panels: Vec<Panel>,
   title: &str,
+  figure: &Figure,
 ) -> Result<(), Box<dyn std::error::Error>> {
-  let root = SVGBackend::new(file, (750, 750)).into_drawing_area();
-  root.fill(&BLACK.mix(0.4))?;
-  let mut chart = ChartBuilder::on(&root)
-    .caption(title, ("Arial", 50).into_font())
+  match figure.format {
+    Format::Svg => layout(
+      SVGBackend::new(file, figure.size).into_drawing_area(),
+      panels,
+      title,
+      figure,
+    ),
+    Format::Png => layout(
+      BitMapBackend::new(file, figure.size).into_drawing_area(),
+      panels,
+      title,
+      figure,
+    ),
+  }
+}
+
+// the rows and columns of the squarest grid with room for n panels, wider than it is tall
+fn grid(n: usize) -> (usize, usize) {
+  let columns = (1..=n.max(1)).find(|c| c * c >= n).unwrap_or(1);
+  (n.max(1).div_ceil(columns), columns)
+}
+
+// a lone panel fills the figure, while several share a grid under the title of the figure
+fn layout<DB: DrawingBackend>(
+  root: DrawingArea<DB, Shift>,
+  panels: Vec<Panel>,
+  title: &str,
+  figure: &Figure,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+  DB::ErrorType: 'static,
+{
+  root.fill(&figure.theme.background())?;
+  if let [panel] = &panels[..] {
+    let caption = panel.title.as_deref().unwrap_or(title);
+    render(&root, panel, (caption, 50), figure)?;
+  } else {
+    let area = root.titled(title, ("Arial", 50).into_font())?;
+    for (area, panel) in area.split_evenly(grid(panels.len())).iter().zip(&panels) {
+      render(
+        area,
+        panel,
+        (panel.title.as_deref().unwrap_or(""), 30),
+        figure,
+      )?;
+    }
+  }
+  root.present()?;
+  Ok(())
+}
+
+// one value of a curve at x, if it has a finite one there
+fn value(ast: &AST, branch: usize, x: f64) -> Option<f64> {
+  eval(ast.clone(), x)
+    .ok()?
+    .get(branch)
+    .cloned()
+    .filter(|y| y.is_finite())
+}
+
+// whether a curve jumps between two neighbouring samples rather than passing through the values between,
+// found by following the half with the bigger change until the interval is too small for a slope to matter
+fn jumps(ast: &AST, branch: usize, from: (f64, f64), to: (f64, f64)) -> bool {
+  let gap = (to.1 - from.1).abs();
+  if gap == 0f64 {
+    return false;
+  }
+  let (mut a, mut b) = (from, to);
+  for _ in 0..30 {
+    let x = (a.0 + b.0) / 2f64;
+    let Some(y) = value(ast, branch, x) else {
+      return true;
+    };
+    if (y - a.1).abs() > (b.1 - y).abs() {
+      b = (x, y);
+    } else {
+      a = (x, y);
+    }
+  }
+  (b.1 - a.1).abs() > gap * 1e-3
+}
+
+// the sampled points of one branch of a curve, split wherever it is undefined or jumps
+fn segments(ast: &AST, branch: usize, figure: &Figure) -> Vec<Vec<(f64, f64)>> {
+  let mut segments: Vec<Vec<(f64, f64)>> = Vec::new();
+  let mut last: Option<(f64, f64)> = None;
+  for x in (0..=400).map(|x| figure.x.0 + (figure.x.1 - figure.x.0) * x as f64 / 400.0) {
+    match value(ast, branch, x) {
+      Some(y) => {
+        if last.is_none_or(|last| jumps(ast, branch, last, (x, y))) {
+          segments.push(Vec::new());
+        }
+        segments.last_mut().unwrap().push((x, y));
+        last = Some((x, y));
+      }
+      None => last = None,
+    }
+  }
+  segments
+}
+
+// the pieces of a segment inside the visible y range, ending where it crosses the edge. a plain line
+// is clipped when drawn, but a dashed one would otherwise be flattened along the edge
+fn clip(segment: Vec<(f64, f64)>, figure: &Figure) -> Vec<Vec<(f64, f64)>> {
+  let inside = |p: &(f64, f64)| (figure.y.0..=figure.y.1).contains(&p.1);
+  let edge = |a: (f64, f64), b: (f64, f64)| {
+    let y = if a.1.max(b.1) > figure.y.1 {
+      figure.y.1
+    } else {
+      figure.y.0
+    };
+    (a.0 + (b.0 - a.0) * (y - a.1) / (b.1 - a.1), y)
+  };
+  let mut pieces: Vec<Vec<(f64, f64)>> = Vec::new();
+  let mut last: Option<(f64, f64)> = None;
+  for p in segment {
+    match (last, inside(&p)) {
+      (Some(l), true) if !inside(&l) => pieces.push(vec![edge(l, p), p]),
+      (None, true) => pieces.push(vec![p]),
+      (_, true) => pieces.last_mut().unwrap().push(p),
+      (Some(l), false) if inside(&l) => pieces.last_mut().unwrap().push(edge(l, p)),
+      _ => {}
+    }
+    last = Some(p);
+  }
+  pieces
+}
+
+// draw one panel with its caption and font size
+fn render<DB: DrawingBackend>(
+  root: &DrawingArea<DB, Shift>,
+  panel: &Panel,
+  caption: (&str, u32),
+  figure: &Figure,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+  DB::ErrorType: 'static,
+{
+  let theme = figure.theme;
+  let figure = &Figure {
+    x: panel.x,
+    y: panel.y,
+    ..figure.clone()
+  };
+  let statement = &panel.curves;
+  let mut chart = ChartBuilder::on(root)
+    .caption(caption.0, ("Arial", caption.1).into_font())
     .margin(5)
     .x_label_area_size(30)
     .y_label_area_size(30)
-    .build_cartesian_2d(-20f64..20f64, -20f64..20f64)?;
+    .build_cartesian_2d(figure.x.0..figure.x.1, figure.y.0..figure.y.1)?;
 
   chart.configure_mesh().draw()?;
 
@@ -22,46 +361,195 @@ pub fn draw(
     .map(|v| v as f64 / statement.len() as f64)
     .collect();
   for i in 0..statement.len() {
-    match statement[i].0.clone() {
-      AST::Identity(identity) => {
-        let cs = colours.clone();
-        println!("{}", statement[i].1.clone());
-        chart
-          .draw_series(LineSeries::new(
-            {
-              let mut po = Vec::<(f64, f64)>::new();
-              let mut no = Vec::<(f64, f64)>::new();
-              for x in (-200..=200).map(|x| x as f64 / 10.0) {
-                let a = eval(*identity.identity[1].clone(), x);
-                if !a[0].is_nan() {
-                  po.push((x, a[0]));
-                }
-                if a.len() > 1 && !a[1].is_nan() {
-                  no.push((x, a[1]));
-                }
-              }
-              no.reverse();
-              po.extend(no);
-              po
-            },
-            HSLColor(colours[i], 1f64, 0.5f64),
-          ))?
-          .label(statement[i].1.clone())
-          .legend(move |(x, y)| {
-            PathElement::new(
-              vec![(x, y), (x + 20, y)],
-              HSLColor(cs[i], 1f64, 0.5f64).filled(),
-            )
+    let style = &statement[i].2;
+    let colour = match style.colour {
+      Some((r, g, b)) => RGBColor(r, g, b).to_rgba(),
+      None => HSLColor(colours[i], 1f64, theme.lightness()).to_rgba(),
+    };
+    let line = colour.stroke_width(style.width);
+    let (dash, gap) = style.dashes();
+    if let AST::Identity(identity) = statement[i].0.clone() {
+      let mut po = if vertical(&statement[i].0) {
+        // `x = x0` is not a curve of y against x, so it runs from the bottom of the panel to the top
+        eval(*identity.identity[1].clone(), 0f64)
+          .unwrap_or_default()
+          .into_iter()
+          .filter(|x| (figure.x.0..=figure.x.1).contains(x))
+          .map(|x| vec![(x, figure.y.0), (x, figure.y.1)])
+          .collect()
+      } else {
+        let mut po = segments(&identity.identity[1], 0, figure);
+        let mut no = segments(&identity.identity[1], 1, figure);
+        // the second branch runs back from the end of the first, closing shapes such as circles
+        no.reverse();
+        no.iter_mut().for_each(|segment| segment.reverse());
+        if let (Some(last), false) = (po.last_mut(), no.is_empty()) {
+          last.extend(no.remove(0));
+        }
+        po.extend(no);
+        po
+      };
+      if po.is_empty() {
+        po.push(Vec::new());
+      }
+      // every piece is drawn in the same colour, with one entry in the legend
+      for (k, segment) in po.into_iter().enumerate() {
+        let series = match style.dash {
+          Dash::Solid => chart.draw_series(LineSeries::new(segment, line))?,
+          _ => chart.draw_series(
+            clip(segment, figure)
+              .into_iter()
+              .flat_map(|piece| DashedLineSeries::new(piece, dash, gap, line)),
+          )?,
+        };
+        if k == 0 {
+          series.label(statement[i].1.clone()).legend(move |(x, y)| {
+            DashedPathElement::new(vec![(x, y), (x + 20, y)], dash, gap, line)
           });
+        }
       }
-      _ => {}
+    } else if let AST::Points(p) = &statement[i].0 {
+      // a wider style draws bigger points, the default width giving a radius of 4
+      let radius = 3 + style.width;
+      let scatter = p.points.iter().filter_map(|(x, y)| match (&**x, &**y) {
+        (AST::Number(x), AST::Number(y)) => Some(Circle::new((*x, *y), radius, colour.filled())),
+        _ => None,
+      });
+      chart
+        .draw_series(scatter)?
+        .label(statement[i].1.clone())
+        .legend(move |(x, y)| Circle::new((x + 10, y), radius, colour.filled()));
     }
   }
+
+  // mark and label the points of interest
+  chart.draw_series(
+    panel
+      .points
+      .iter()
+      .filter(|(x, y)| {
+        (figure.x.0..=figure.x.1).contains(x) && (figure.y.0..=figure.y.1).contains(y)
+      })
+      .map(|&(x, y)| {
+        EmptyElement::at((x, y))
+          + Circle::new((0, 0), 4, theme.foreground().filled())
+          + Text::new(
+            format!("({:.2}, {:.2})", x, y),
+            (6, -16),
+            ("Arial", 14).into_font().color(&theme.foreground()),
+          )
+      }),
+  )?;
+
   chart
     .configure_series_labels()
-    .background_style(&WHITE.mix(0.8))
-    .border_style(&BLACK)
+    .background_style(WHITE.mix(0.8))
+    .border_style(BLACK)
     .draw()?;
-
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::syntax::parser::Parser;
+
+  fn curve(input: &str) -> AST {
+    *Parser::new(input.to_string()).parse_expression().unwrap()
+  }
+
+  // a step is drawn as separate pieces, while a steep but connected curve is not broken
+  #[test]
+  fn discontinuities() {
+    let figure = Figure {
+      x: (-2f64, 2f64),
+      ..Figure::default()
+    };
+    assert_eq!(segments(&curve("floor(x)"), 0, &figure).len(), 5);
+    assert_eq!(segments(&curve("{x < 0: -1; 1}"), 0, &figure).len(), 2);
+    assert_eq!(segments(&curve("atan(1000x)"), 0, &figure).len(), 1);
+    assert_eq!(segments(&curve("sqrt(1 - x^2)"), 0, &figure).len(), 1);
+    assert_eq!(segments(&curve("1 / x"), 0, &figure).len(), 2);
+  }
+
+  // panels fill a grid row by row, adding a column before a row
+  #[test]
+  fn grids() {
+    let grids: Vec<(usize, usize)> = (1..=7).map(grid).collect();
+    assert_eq!(
+      grids,
+      vec![(1, 1), (1, 2), (2, 2), (2, 2), (2, 3), (2, 3), (3, 3)]
+    );
+  }
+
+  // a dashed curve stops at the edge of the view rather than running along it
+  #[test]
+  fn clipping() {
+    let figure = Figure {
+      y: (-1f64, 1f64),
+      ..Figure::default()
+    };
+    let segment = vec![
+      (-2f64, 3f64),
+      (0f64, 0f64),
+      (1f64, 0.5f64),
+      (2f64, -3f64),
+      (3f64, 0f64),
+    ];
+    assert_eq!(
+      clip(segment, &figure),
+      vec![
+        vec![
+          (-2f64 + 2f64 * 2f64 / 3f64, 1f64),
+          (0f64, 0f64),
+          (1f64, 0.5f64),
+          (1f64 + 1.5f64 / 3.5f64, -1f64)
+        ],
+        vec![(3f64 - 1f64 / 3f64, -1f64), (3f64, 0f64)],
+      ]
+    );
+    assert!(clip(vec![(0f64, 5f64), (1f64, 6f64)], &figure).is_empty());
+  }
+
+  // options set the style one at a time, and a bad value names what was expected
+  #[test]
+  fn styles() {
+    let commands = |input: &str| {
+      let AST::Statement(statement) = *Parser::new(input.to_string()).parse().unwrap()[0].clone()
+      else {
+        panic!("expected a statement");
+      };
+      statement.commands
+    };
+    assert_eq!(
+      Style::from(&commands(
+        "y = x; @draw color=#f80 style=dotted @panel x=0..1 @intercepts width=3 label=\"profit\""
+      )),
+      Ok(Style {
+        colour: Some((255, 136, 0)),
+        dash: Dash::Dotted,
+        width: 3,
+        label: Some("profit".to_string()),
+      })
+    );
+    // the options of `@tangent` and `@panel` leave the curve alone
+    assert_eq!(
+      Style::from(&commands("y = x; @tangent 1 width=3 @panel x=0..1")),
+      Ok(Style::default())
+    );
+    let mut style = Style::default();
+    let word = |w: &str| Argument::Text(w.to_string());
+    assert_eq!(style.set("colour", &word("#00ff7F")), Ok(()));
+    assert_eq!(style.colour, Some((0, 255, 127)));
+    assert!(style
+      .set("color", &word("red"))
+      .unwrap_err()
+      .contains("#rrggbb"));
+    assert!(style.set("width", &word("0")).is_err());
+    assert!(style.set("width", &word("2.5")).is_err());
+    assert!(style.set("style", &word("wavy")).is_err());
+    assert!(style.set("opacity", &word("1")).is_err());
+    let range = Argument::Range(Box::new(AST::Number(1f64)), Box::new(AST::Number(2f64)));
+    assert!(style.set("width", &range).is_err());
+  }
+}
@@ -0,0 +1,157 @@
+use super::{eval, expand, pretty, quadratic::Quadratic, substitute, unique, variables};
+use crate::syntax::parser::AST;
+use std::collections::BTreeMap;
+
+// the working of solving two equations by putting one into the other
+#[derive(Debug, Clone)]
+pub struct Elimination {
+  pub var: String,
+  // `var = ...` from whichever equation var is linear in
+  pub solved: AST,
+  // the other equation with var replaced, leaving one variable
+  pub substituted: AST,
+  pub remaining: String,
+  pub quadratic: Quadratic,
+  pub solution: AST,
+  // every pair of values where both equations hold, by variable name
+  pub points: Vec<BTreeMap<String, f64>>,
+}
+
+fn zero(ast: &AST) -> bool {
+  matches!(expand(ast.clone()), AST::Number(n) if n == 0f64)
+}
+
+fn rhs(ast: &AST) -> Result<&AST, String> {
+  match ast {
+    AST::Identity(identity) if identity.identity.len() == 2 => Ok(&identity.identity[1]),
+    _ => Err("Expected an equation of the form a = b.".to_string()),
+  }
+}
+
+// every finite value of an expression that has no variables left in it
+fn values(ast: &AST) -> Result<Vec<f64>, String> {
+  let unknown = variables(ast);
+  if !unknown.is_empty() {
+    return Err(format!(
+      "Cannot find a value while {} is unknown.",
+      unknown.into_iter().collect::<Vec<_>>().join(", ")
+    ));
+  }
+  Ok(
+    eval(ast.clone(), 0f64)
+      .map_err(|e| e.to_string())?
+      .into_iter()
+      .filter(|n| n.is_finite())
+      .collect(),
+  )
+}
+
+// remove var from a pair of equations by solving one for it and substituting into the other, then
+// solve for the variable left and put each of its roots back to find var
+pub fn eliminate(first: &AST, second: &AST, var: &str) -> Result<Elimination, String> {
+  let pair = [expand(first.clone()), expand(second.clone())];
+  // only an equation that is linear in var gives a single expression to put in its place
+  let (i, quad) = pair
+    .iter()
+    .enumerate()
+    .filter_map(|(i, ast)| Some((i, Quadratic::from(ast.clone(), var.to_string()).ok()?)))
+    .find(|(_, q)| zero(&q.a) && !zero(&q.b))
+    .ok_or(format!("Neither equation is linear in {}.", var))?;
+  let solved = expand(quad.solve()?);
+  let replacement = rhs(&solved)?.clone();
+
+  let substituted = expand(substitute(pair[1 - i].clone(), var, &replacement));
+  let remaining = match variables(&substituted).into_iter().collect::<Vec<_>>()[..] {
+    [ref v] => v.clone(),
+    _ => {
+      return Err(format!(
+        "Expected one variable to be left after eliminating {}.",
+        var
+      ))
+    }
+  };
+  let quadratic = Quadratic::from(substituted.clone(), remaining.clone()).map_err(|_| {
+    format!(
+      "Cannot solve {} for {}, it is not a quadratic.",
+      pretty(substituted.clone()),
+      remaining
+    )
+  })?;
+  if zero(&quadratic.a) && zero(&quadratic.b) {
+    return Err("The equations do not meet at separate points.".to_string());
+  }
+  let solution = expand(quadratic.solve()?);
+
+  let mut pairs: Vec<(f64, f64)> = Vec::new();
+  for root in values(rhs(&solution)?)? {
+    let at = substitute(replacement.clone(), &remaining, &AST::Number(root));
+    for value in values(&at)? {
+      pairs.push((root, value));
+    }
+  }
+  let points = unique(pairs)
+    .into_iter()
+    .map(|(root, value)| BTreeMap::from([(remaining.clone(), root), (var.to_string(), value)]))
+    .collect();
+  Ok(Elimination {
+    var: var.to_string(),
+    solved,
+    substituted,
+    remaining,
+    quadratic,
+    solution,
+    points,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::syntax::parser::Parser;
+
+  fn points(first: &str, second: &str, var: &str) -> Result<Vec<(f64, f64)>, String> {
+    let parse = |input: &str| *Parser::new(input.to_string() + ";").parse().unwrap()[0].clone();
+    let elimination = eliminate(&parse(first), &parse(second), var)?;
+    Ok(
+      elimination
+        .points
+        .iter()
+        .map(|p| (p["x"], p["y"]))
+        .collect(),
+    )
+  }
+
+  // a line crosses a circle twice, whichever variable is eliminated
+  #[test]
+  fn line_and_circle() {
+    for var in ["x", "y"] {
+      let mut found = points("y = x + 1", "x^2 + y^2 = 25", var).unwrap();
+      found.sort_by(|a, b| a.0.total_cmp(&b.0));
+      assert_eq!(found, vec![(-4f64, -3f64), (3f64, 4f64)], "{}", var);
+    }
+  }
+
+  // either equation can be the linear one, and a tangent meets once
+  #[test]
+  fn tangent() {
+    assert_eq!(points("y = x^2", "y = 2x - 1", "y"), Ok(vec![(1f64, 1f64)]));
+    assert_eq!(points("x^2 + y^2 = 1", "x = 2", "x"), Ok(vec![]));
+  }
+
+  // nothing to substitute when var is squared in both, and parallel lines never meet
+  #[test]
+  fn not_linear() {
+    assert_eq!(
+      points("x^2 + y^2 = 1", "x^2 = y", "x"),
+      Err("Neither equation is linear in x.".to_string())
+    );
+    assert_eq!(
+      points("y = x + 1", "y = x + 2", "y"),
+      Err("The equations do not meet at separate points.".to_string())
+    );
+    assert_eq!(
+      points("x^2 + y^2 = 1", "x^2 = y", "y"),
+      Err("Cannot solve x^2 + (-x^2 / -1)^2 = 1 for x, it is not a quadratic.".to_string())
+    );
+  }
+}
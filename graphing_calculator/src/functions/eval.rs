@@ -1,15 +1,38 @@
-use crate::syntax::parser::{Sign, AST};
+use crate::syntax::parser::{Relation, Sign, AST};
+use crate::Error;
+use std::f64::consts::{E, PI};
 
-pub fn eval(expr: AST, x: f64) -> Vec<f64> {
-  match expr.clone() {
+// names that always stand for the same number
+pub fn constant(name: &str) -> Option<f64> {
+  match name {
+    "π" | "pi" => Some(PI),
+    "e" => Some(E),
+    _ => None,
+  }
+}
+
+// n! for whole numbers from 0, anything else has no value
+pub fn factorial(n: f64) -> f64 {
+  if n < 0f64 || n.fract() != 0f64 {
+    return f64::NAN;
+  }
+  (1..=n as u64).fold(1f64, |product, i| product * i as f64)
+}
+
+// every value of an expression at x, failing on a name that is neither x nor a constant
+pub fn eval(expr: AST, x: f64) -> Result<Vec<f64>, Error> {
+  Ok(match expr.clone() {
     AST::Number(n) => vec![n],
     AST::Variable(v) => match v.as_str() {
       "x" => vec![x],
-      _ => panic!("Unknown variable {}", v),
+      _ => match constant(&v) {
+        Some(c) => vec![c],
+        None => return Err(Error::UnknownVariable(v)),
+      },
     },
     AST::Unary(u) => {
       let mut o = Vec::<f64>::new();
-      for n in eval(*u.unary.clone(), x) {
+      for n in eval(*u.unary.clone(), x)? {
         o.extend(match u.sign {
           Sign::Add => vec![n],
           Sign::Sub => vec![-n],
@@ -20,15 +43,15 @@ pub fn eval(expr: AST, x: f64) -> Vec<f64> {
       o
     }
     AST::Expr(e) => {
-      if e.expr.len() == 0 {
-        return vec![0f64];
+      if e.expr.is_empty() {
+        return Ok(vec![0f64]);
       }
-      let mut o = eval(*e.expr[0].clone(), x);
+      let mut o = eval(*e.expr[0].clone(), x)?;
       for i in 1..e.expr.len() {
         let oc = o.clone();
         o.clear();
         for j in oc {
-          for n in eval(*e.expr[i].clone(), x) {
+          for n in eval(*e.expr[i].clone(), x)? {
             o.extend(match e.sign {
               Sign::Add => vec![j + n],
               Sign::Sub => vec![j - n],
@@ -43,18 +66,20 @@ pub fn eval(expr: AST, x: f64) -> Vec<f64> {
       o
     }
     AST::Term(t) => {
-      let mut o = eval(*t.term[0].clone(), x);
+      let mut o = eval(*t.term[0].clone(), x)?;
       for i in 1..t.term.len() {
         let oc = o.clone();
         o.clear();
         for j in oc {
-          for n in eval(*t.term[i].clone(), x) {
+          for n in eval(*t.term[i].clone(), x)? {
             o.extend(match t.sign {
               Sign::Add => vec![j + n],
               Sign::Sub => vec![j - n],
               Sign::AddSub => vec![j + n, j - n],
               Sign::Mul => vec![j * n],
               Sign::Div => vec![j / n],
+              // the remainder takes the sign of the modulus, so `-1 mod 3` is 2
+              Sign::Mod => vec![j - n * (j / n).floor()],
               _ => vec![0f64],
             });
           }
@@ -65,8 +90,8 @@ pub fn eval(expr: AST, x: f64) -> Vec<f64> {
     AST::Index(e) => match e.sign {
       Sign::Pow => {
         let mut o = Vec::<f64>::new();
-        for i in eval(*e.index.0.clone(), x) {
-          for j in eval(*e.index.1.clone(), x) {
+        for i in eval(*e.index.0.clone(), x)? {
+          for j in eval(*e.index.1.clone(), x)? {
             o.push(f64::powf(i, j));
           }
         }
@@ -74,66 +99,142 @@ pub fn eval(expr: AST, x: f64) -> Vec<f64> {
       }
       _ => vec![0f64],
     },
-    AST::Identity(s) => eval(*s.identity[1].clone(), x),
-    AST::Statement(s) => eval(*s.statement, x),
+    AST::Identity(s) => eval(*s.identity[1].clone(), x)?,
+    AST::Statement(s) => eval(*s.statement, x)?,
+    // a list of points is not a function of x
+    AST::Points(_) => vec![],
+    // a comparison is 1 when it holds and 0 when it does not
+    AST::Compare(c) => {
+      let mut o = Vec::<f64>::new();
+      for i in eval(*c.compare.0.clone(), x)? {
+        for j in eval(*c.compare.1.clone(), x)? {
+          let holds = match c.relation {
+            Relation::Less => i < j,
+            Relation::Greater => i > j,
+            Relation::LessEqual => i <= j,
+            Relation::GreaterEqual => i >= j,
+          };
+          o.push(if holds { 1f64 } else { 0f64 });
+        }
+      }
+      o
+    }
+    AST::Piecewise(p) => {
+      for (condition, value) in p.piecewise {
+        if eval(*condition, x)?
+          .iter()
+          .any(|c| *c != 0f64 && !c.is_nan())
+        {
+          return eval(*value, x);
+        }
+      }
+      match p.otherwise {
+        Some(otherwise) => eval(*otherwise, x)?,
+        None => vec![f64::NAN],
+      }
+    }
     AST::Call(c) => match c.name.as_str() {
       "sqrt" => {
         let mut o = Vec::<f64>::new();
-        for n in eval(*c.call[0].clone(), x) {
+        for n in eval(*c.call[0].clone(), x)? {
           o.push(f64::sqrt(n));
         }
         o
       }
       "sin" => {
         let mut o = Vec::<f64>::new();
-        for n in eval(*c.call[0].clone(), x) {
+        for n in eval(*c.call[0].clone(), x)? {
           o.push(f64::sin(n));
         }
         o
       }
       "cos" => {
         let mut o = Vec::<f64>::new();
-        for n in eval(*c.call[0].clone(), x) {
+        for n in eval(*c.call[0].clone(), x)? {
           o.push(f64::cos(n));
         }
         o
       }
       "tan" => {
         let mut o = Vec::<f64>::new();
-        for n in eval(*c.call[0].clone(), x) {
+        for n in eval(*c.call[0].clone(), x)? {
           o.push(f64::tan(n));
         }
         o
       }
       "asin" => {
         let mut o = Vec::<f64>::new();
-        for n in eval(*c.call[0].clone(), x) {
+        for n in eval(*c.call[0].clone(), x)? {
           o.push(f64::asin(n));
         }
         o
       }
       "acos" => {
         let mut o = Vec::<f64>::new();
-        for n in eval(*c.call[0].clone(), x) {
+        for n in eval(*c.call[0].clone(), x)? {
           o.push(f64::acos(n));
         }
         o
       }
       "atan" => {
         let mut o = Vec::<f64>::new();
-        for n in eval(*c.call[0].clone(), x) {
+        for n in eval(*c.call[0].clone(), x)? {
           o.push(f64::atan(n));
         }
         o
       }
       "ln" => {
         let mut o = Vec::<f64>::new();
-        for n in eval(*c.call[0].clone(), x) {
+        for n in eval(*c.call[0].clone(), x)? {
           o.push(f64::ln(n));
         }
         o
       }
+      "abs" => {
+        let mut o = Vec::<f64>::new();
+        for n in eval(*c.call[0].clone(), x)? {
+          o.push(f64::abs(n));
+        }
+        o
+      }
+      "floor" => {
+        let mut o = Vec::<f64>::new();
+        for n in eval(*c.call[0].clone(), x)? {
+          o.push(f64::floor(n));
+        }
+        o
+      }
+      "ceil" => {
+        let mut o = Vec::<f64>::new();
+        for n in eval(*c.call[0].clone(), x)? {
+          o.push(f64::ceil(n));
+        }
+        o
+      }
+      "factorial" => {
+        let mut o = Vec::<f64>::new();
+        for n in eval(*c.call[0].clone(), x)? {
+          o.push(factorial(n));
+        }
+        o
+      }
+      // every value of every argument, so `min(±x, 0)` takes the smallest of all three
+      "min" => vec![arguments(&c.call, x)?
+        .into_iter()
+        .fold(f64::INFINITY, f64::min)],
+      "max" => vec![arguments(&c.call, x)?
+        .into_iter()
+        .fold(f64::NEG_INFINITY, f64::max)],
       _ => vec![0f64],
     },
+  })
+}
+
+// every value of every argument of a call
+fn arguments(call: &[Box<AST>], x: f64) -> Result<Vec<f64>, Error> {
+  let mut o = Vec::<f64>::new();
+  for a in call {
+    o.extend(eval(*a.clone(), x)?);
   }
+  Ok(o)
 }
@@ -1,4 +1,23 @@
-use crate::syntax::parser::{Call, Expr, Identity, Sign, Term, AST};
+use super::eval::factorial;
+use super::round;
+use crate::syntax::parser::{
+  Call, Compare, Expr, Identity, Index, Piecewise, Points, Sign, Term, Unary, AST,
+};
+
+// a quotient of numbers such as `6 / 3`, when its value has no more places than are written out,
+// so `1 / 4` is 0.25 but `1 / 3` is left as it is
+fn quotient(parts: &[Box<AST>]) -> Option<f64> {
+  let numbers = parts
+    .iter()
+    .map(|part| match **part {
+      AST::Number(x) => Some(x),
+      _ => None,
+    })
+    .collect::<Option<Vec<f64>>>()?;
+  let (first, rest) = numbers.split_first()?;
+  let q = rest.iter().fold(*first, |q, d| q / d);
+  (q.is_finite() && round(q) == q).then_some(q)
+}
 
 pub fn expand(ast: AST) -> AST {
   match ast.clone() {
@@ -8,32 +27,59 @@ pub fn expand(ast: AST) -> AST {
         Sign::Add => n,
         Sign::Sub => match n {
           AST::Number(n) => AST::Number(-n),
-          _ => AST::Unary(u),
+          _ => AST::Unary(Unary {
+            sign: u.sign,
+            unary: Box::new(n),
+          }),
         },
-        _ => AST::Unary(u),
+        _ => AST::Unary(Unary {
+          sign: u.sign,
+          unary: Box::new(n),
+        }),
+      }
+    }
+    AST::Expr(e) if e.sign == Sign::Sub => {
+      let mut head: Option<AST> = None;
+      let mut o: Vec<Box<AST>> = Vec::new();
+      let mut n = 0f64;
+      // everything after the first operand is subtracted
+      for (k, i) in e.expr.into_iter().enumerate() {
+        match expand(*i) {
+          AST::Number(x) if k == 0 => n += x,
+          AST::Number(x) => n -= x,
+          j if k == 0 => head = Some(j),
+          j => o.push(Box::new(j)),
+        }
+      }
+      match head {
+        Some(h) => {
+          if n != 0f64 {
+            o.push(Box::new(AST::Number(-n)));
+          }
+          o.insert(0, Box::new(h));
+        }
+        None => o.insert(0, Box::new(AST::Number(n))),
+      }
+      if o.len() == 1 {
+        *o.remove(0)
+      } else {
+        AST::Expr(Expr {
+          expr: o,
+          sign: Sign::Sub,
+        })
       }
     }
     AST::Expr(e) => {
       let mut o: Vec<Box<AST>> = Vec::new();
       let mut n = 0f64;
-      fn expand_expr(e: Expr, mut o: &mut Vec<Box<AST>>, mut n: &mut f64) {
+      fn expand_expr(e: Expr, o: &mut Vec<Box<AST>>, n: &mut f64) {
         for i in e.expr {
           let j = expand(*i);
           match j {
-            AST::Number(x) => match e.sign {
-              Sign::Add => *n += x,
-              Sign::Sub => *n -= x,
-              Sign::AddSub => o.push(Box::new(j.clone())),
-              _ => {}
-            },
-            AST::Expr(x) => {
-              if e.sign == Sign::Add {
-                expand_expr(x, &mut o, &mut n)
-              } else {
-                o.push(Box::new(expand(AST::Expr(x))))
-              }
-            }
-            _ => o.push(Box::new(j.clone())),
+            AST::Number(x) if e.sign == Sign::Add => *n += x,
+            // only a nested sum can be flattened into a sum
+            AST::Expr(x) if e.sign == Sign::Add && x.sign == Sign::Add => expand_expr(x, o, n),
+            _ => o.push(Box::new(j)),
           };
         }
       }
@@ -41,10 +87,10 @@ pub fn expand(ast: AST) -> AST {
       if n != 0f64 {
         o.splice(0..0, [Box::new(AST::Number(n))]);
       }
-      if o.len() == 0 {
+      if o.is_empty() {
         AST::Number(0f64)
       } else if o.len() == 1 {
-        *o.get(0).unwrap().clone()
+        *o.first().unwrap().clone()
       } else {
         AST::Expr(Expr {
           expr: o,
@@ -55,7 +101,7 @@ pub fn expand(ast: AST) -> AST {
     AST::Term(t) => {
       let mut o: Vec<Box<AST>> = Vec::new();
       let mut n = 1f64;
-      fn expand_term(t: Term, mut o: &mut Vec<Box<AST>>, mut n: &mut f64) {
+      fn expand_term(t: Term, o: &mut Vec<Box<AST>>, n: &mut f64) {
         for i in t.term {
           let j = expand(*i);
           match j {
@@ -65,7 +111,7 @@ pub fn expand(ast: AST) -> AST {
             },
             AST::Term(x) => {
               if t.sign == Sign::Mul {
-                expand_term(x, &mut o, &mut n)
+                expand_term(x, o, n)
               } else {
                 o.push(Box::new(expand(AST::Term(x))))
               }
@@ -75,11 +121,16 @@ pub fn expand(ast: AST) -> AST {
         }
       }
       expand_term(t.clone(), &mut o, &mut n);
+      if t.sign == Sign::Div {
+        if let Some(q) = quotient(&o) {
+          return AST::Number(q);
+        }
+      }
       if n != 1f64 {
         o.push(Box::new(AST::Number(n)));
       }
       if o.len() == 1 {
-        *o.get(0).unwrap().clone()
+        *o.first().unwrap().clone()
       } else {
         AST::Term(Term {
           term: o,
@@ -93,9 +144,15 @@ pub fn expand(ast: AST) -> AST {
       match i {
         AST::Number(x) => match j {
           AST::Number(y) => AST::Number(f64::powf(x, y)),
-          _ => AST::Index(e),
+          _ => AST::Index(Index {
+            sign: e.sign,
+            index: (Box::new(AST::Number(x)), Box::new(j)),
+          }),
         },
-        _ => AST::Index(e),
+        _ => AST::Index(Index {
+          sign: e.sign,
+          index: (Box::new(i), Box::new(j)),
+        }),
       }
     }
     AST::Identity(s) => AST::Identity(Identity {
@@ -107,28 +164,58 @@ pub fn expand(ast: AST) -> AST {
     }),
     AST::Statement(s) => expand(*s.statement),
     AST::Call(c) => {
-      let a = expand(*c.call[0].clone());
-      match a {
-        AST::Number(x) => match c.name.as_str() {
-          "sqrt" => AST::Number(f64::sqrt(x)),
-          "sin" => AST::Number(f64::sin(x)),
-          "cos" => AST::Number(f64::cos(x)),
-          "tan" => AST::Number(f64::tan(x)),
-          "asin" => AST::Number(f64::asin(x)),
-          "acos" => AST::Number(f64::acos(x)),
-          "atan" => AST::Number(f64::atan(x)),
-          "ln" => AST::Number(f64::ln(x)),
-          _ => AST::Call(Call {
-            call: vec![Box::new(a)],
-            name: c.name,
-          }),
-        },
-        _ => AST::Call(Call {
-          call: vec![Box::new(a)],
-          name: c.name,
-        }),
+      let call: Vec<Box<AST>> = c.call.into_iter().map(|a| Box::new(expand(*a))).collect();
+      let numbers: Option<Vec<f64>> = call
+        .iter()
+        .map(|a| match **a {
+          AST::Number(x) => Some(x),
+          _ => None,
+        })
+        .collect();
+      let folded = match (c.name.as_str(), numbers.as_deref()) {
+        ("sqrt", Some([x])) => Some(f64::sqrt(*x)),
+        ("sin", Some([x])) => Some(f64::sin(*x)),
+        ("cos", Some([x])) => Some(f64::cos(*x)),
+        ("tan", Some([x])) => Some(f64::tan(*x)),
+        ("asin", Some([x])) => Some(f64::asin(*x)),
+        ("acos", Some([x])) => Some(f64::acos(*x)),
+        ("atan", Some([x])) => Some(f64::atan(*x)),
+        ("ln", Some([x])) => Some(f64::ln(*x)),
+        ("abs", Some([x])) => Some(f64::abs(*x)),
+        ("floor", Some([x])) => Some(f64::floor(*x)),
+        ("ceil", Some([x])) => Some(f64::ceil(*x)),
+        ("factorial", Some([x])) => Some(factorial(*x)),
+        ("min", Some(xs)) => Some(xs.iter().cloned().fold(f64::INFINITY, f64::min)),
+        ("max", Some(xs)) => Some(xs.iter().cloned().fold(f64::NEG_INFINITY, f64::max)),
+        _ => None,
+      };
+      match folded {
+        Some(x) => AST::Number(x),
+        None => AST::Call(Call { call, name: c.name }),
       }
     }
+    AST::Compare(c) => AST::Compare(Compare {
+      relation: c.relation,
+      compare: (
+        Box::new(expand(*c.compare.0)),
+        Box::new(expand(*c.compare.1)),
+      ),
+    }),
+    AST::Piecewise(p) => AST::Piecewise(Piecewise {
+      piecewise: p
+        .piecewise
+        .into_iter()
+        .map(|(condition, value)| (Box::new(expand(*condition)), Box::new(expand(*value))))
+        .collect(),
+      otherwise: p.otherwise.map(|o| Box::new(expand(*o))),
+    }),
+    AST::Points(p) => AST::Points(Points {
+      points: p
+        .points
+        .into_iter()
+        .map(|(x, y)| (Box::new(expand(*x)), Box::new(expand(*y))))
+        .collect(),
+    }),
     _ => ast,
   }
 }
@@ -0,0 +1,257 @@
+use super::round;
+use crate::syntax::parser::{Expr, Identity, Index, Sign, Term, Unary, AST};
+
+// the curves that can be fitted to points, named as they are after `@fit`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Model {
+  Linear,
+  Quadratic,
+  Exponential,
+}
+
+impl Model {
+  pub fn from(name: &str) -> Option<Model> {
+    match name {
+      "linear" => Some(Model::Linear),
+      "quadratic" => Some(Model::Quadratic),
+      "exp" => Some(Model::Exponential),
+      _ => None,
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Model::Linear => "linear",
+      Model::Quadratic => "quadratic",
+      Model::Exponential => "exp",
+    }
+  }
+}
+
+// the curve of a model closest to a set of points
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fit {
+  pub model: Model,
+  // lowest power first, or the `a` and `b` of `y = ae^(bx)`
+  pub coefficients: Vec<f64>,
+  pub curve: AST,
+  // the coefficient of determination, 1 when every point is on the curve
+  pub r2: f64,
+}
+
+// one term after another, merged into a chain while the sign stays the same as the parser does
+fn chain(sum: AST, sign: Sign, term: AST) -> AST {
+  match sum {
+    AST::Expr(mut e) if e.sign == sign => {
+      e.expr.push(Box::new(term));
+      AST::Expr(e)
+    }
+    sum => AST::Expr(Expr {
+      sign,
+      expr: vec![Box::new(sum), Box::new(term)],
+    }),
+  }
+}
+
+// `c x^n + ... + c`, from the coefficients lowest power first, leaving out the terms that round
+// to zero
+pub fn polynomial(coefficients: &[f64]) -> AST {
+  let x = AST::Variable("x".to_string());
+  let mut out: Option<AST> = None;
+  for (power, c) in coefficients.iter().enumerate().rev() {
+    let c = round(*c);
+    if c == 0f64 {
+      continue;
+    }
+    let power = match power {
+      0 => None,
+      1 => Some(x.clone()),
+      n => Some(AST::Index(Index {
+        sign: Sign::Pow,
+        index: (Box::new(x.clone()), Box::new(AST::Number(n as f64))),
+      })),
+    };
+    // the sign of the first term is kept on it, the rest are added or subtracted
+    let n = if out.is_some() { c.abs() } else { c };
+    let term = match power {
+      None => AST::Number(n),
+      Some(p) if n == 1f64 => p,
+      Some(p) if n == -1f64 => AST::Unary(Unary {
+        sign: Sign::Sub,
+        unary: Box::new(p),
+      }),
+      Some(p) => AST::Term(Term {
+        sign: Sign::Mul,
+        term: vec![Box::new(AST::Number(n)), Box::new(p)],
+      }),
+    };
+    out = Some(match out {
+      None => term,
+      Some(sum) if c < 0f64 => chain(sum, Sign::Sub, term),
+      Some(sum) => chain(sum, Sign::Add, term),
+    });
+  }
+  out.unwrap_or(AST::Number(0f64))
+}
+
+// gaussian elimination with partial pivoting on the rows of an augmented matrix, None when the
+// system has no single solution
+fn solve(mut rows: Vec<Vec<f64>>) -> Option<Vec<f64>> {
+  let n = rows.len();
+  for c in 0..n {
+    let p = (c..n).max_by(|&i, &j| rows[i][c].abs().total_cmp(&rows[j][c].abs()))?;
+    if rows[p][c].abs() < 1e-12 {
+      return None;
+    }
+    rows.swap(c, p);
+    let pivot = rows[c].clone();
+    for (i, row) in rows.iter_mut().enumerate() {
+      if i != c {
+        let m = row[c] / pivot[c];
+        for (cell, p) in row.iter_mut().zip(&pivot).skip(c) {
+          *cell -= m * p;
+        }
+      }
+    }
+  }
+  Some((0..n).map(|i| rows[i][n] / rows[i][i]).collect())
+}
+
+// the polynomial of a degree with the least squared error over the points, lowest power first
+fn least_squares(points: &[(f64, f64)], degree: usize) -> Option<Vec<f64>> {
+  let n = degree + 1;
+  if points.len() < n {
+    return None;
+  }
+  // the normal equations, where row i is `sum x^(i + j) c_j = sum x^i y`
+  let rows = (0..n)
+    .map(|i| {
+      let mut row: Vec<f64> = (0..n)
+        .map(|j| points.iter().map(|p| p.0.powi((i + j) as i32)).sum())
+        .collect();
+      row.push(points.iter().map(|p| p.0.powi(i as i32) * p.1).sum());
+      row
+    })
+    .collect();
+  solve(rows)
+}
+
+// fit a model to points, None when there are too few distinct points or, for an exponential, a y
+// that is not positive
+pub fn fit(points: &[(f64, f64)], model: Model) -> Option<Fit> {
+  let coefficients = match model {
+    Model::Linear => least_squares(points, 1)?,
+    Model::Quadratic => least_squares(points, 2)?,
+    // a straight line through `(x, ln y)`, so `ln y = ln a + bx`
+    Model::Exponential => {
+      if points.iter().any(|p| p.1 <= 0f64) {
+        return None;
+      }
+      let logs: Vec<(f64, f64)> = points.iter().map(|p| (p.0, p.1.ln())).collect();
+      let line = least_squares(&logs, 1)?;
+      vec![line[0].exp(), line[1]]
+    }
+  };
+  let at = |x: f64| match model {
+    Model::Exponential => coefficients[0] * (coefficients[1] * x).exp(),
+    _ => coefficients
+      .iter()
+      .enumerate()
+      .map(|(i, c)| c * x.powi(i as i32))
+      .sum(),
+  };
+  let mean = points.iter().map(|p| p.1).sum::<f64>() / points.len() as f64;
+  let residual: f64 = points.iter().map(|p| (p.1 - at(p.0)).powi(2)).sum();
+  let total: f64 = points.iter().map(|p| (p.1 - mean).powi(2)).sum();
+  let r2 = if total == 0f64 {
+    1f64
+  } else {
+    1f64 - residual / total
+  };
+
+  let body = match model {
+    Model::Exponential => {
+      let b = round(coefficients[1]);
+      let exponent = if b == 1f64 {
+        AST::Variable("x".to_string())
+      } else {
+        AST::Term(Term {
+          sign: Sign::Mul,
+          term: vec![
+            Box::new(AST::Number(b)),
+            Box::new(AST::Variable("x".to_string())),
+          ],
+        })
+      };
+      AST::Term(Term {
+        sign: Sign::Mul,
+        term: vec![
+          Box::new(AST::Number(round(coefficients[0]))),
+          Box::new(AST::Index(Index {
+            sign: Sign::Pow,
+            index: (Box::new(AST::Variable("e".to_string())), Box::new(exponent)),
+          })),
+        ],
+      })
+    }
+    _ => polynomial(&coefficients),
+  };
+  Some(Fit {
+    model,
+    coefficients,
+    curve: AST::Identity(Identity {
+      identity: vec![Box::new(AST::Variable("y".to_string())), Box::new(body)],
+    }),
+    r2,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::functions::pretty;
+
+  fn close(a: &[f64], b: &[f64]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-9)
+  }
+
+  // points on a curve give that curve back exactly
+  #[test]
+  fn exact() {
+    let line = fit(&[(0f64, 1f64), (1f64, 3f64), (2f64, 5f64)], Model::Linear).unwrap();
+    assert!(close(&line.coefficients, &[1f64, 2f64]));
+    assert_eq!(pretty(line.curve), "y = 2x + 1");
+    assert!(close(&[line.r2], &[1f64]));
+
+    let parabola = [-1f64, 0f64, 1f64, 2f64].map(|x| (x, 0.5 * x * x - x - 2f64));
+    let quadratic = fit(&parabola, Model::Quadratic).unwrap();
+    assert_eq!(pretty(quadratic.curve), "y = 0.5x^2 - x - 2");
+
+    let growth = [0f64, 1f64, 2f64].map(|x| (x, 3f64 * (0.5 * x).exp()));
+    assert_eq!(
+      pretty(fit(&growth, Model::Exponential).unwrap().curve),
+      "y = 3e^(0.5x)"
+    );
+  }
+
+  // the lab example, where the points are close to but not on a line
+  #[test]
+  fn scattered() {
+    let found = fit(
+      &[(1f64, 2f64), (2f64, 4.1f64), (3f64, 5.9f64)],
+      Model::Linear,
+    )
+    .unwrap();
+    assert!(close(&found.coefficients, &[0.1, 1.95]));
+    assert!(found.r2 > 0.99 && found.r2 < 1f64);
+  }
+
+  // too few points, points stacked on one x and a y of zero under an exponential cannot be fitted
+  #[test]
+  fn degenerate() {
+    assert!(fit(&[(1f64, 2f64)], Model::Linear).is_none());
+    assert!(fit(&[(1f64, 2f64), (1f64, 3f64), (1f64, 4f64)], Model::Linear).is_none());
+    assert!(fit(&[(0f64, 1f64), (1f64, 0f64)], Model::Exponential).is_none());
+    assert_eq!(Model::from("cubic"), None);
+  }
+}
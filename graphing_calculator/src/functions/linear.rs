@@ -0,0 +1,598 @@
+use super::expand;
+use crate::syntax::parser::{Sign, AST};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// an exact fraction, always stored in lowest terms with a positive denominator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rational {
+  pub num: i128,
+  pub den: i128,
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+  let (mut a, mut b) = (a.abs(), b.abs());
+  while b != 0 {
+    (a, b) = (b, a % b);
+  }
+  a
+}
+
+impl Rational {
+  pub fn new(num: i128, den: i128) -> Rational {
+    if den == 0 {
+      panic!("Rational with a zero denominator.");
+    }
+    let g = gcd(num, den).max(1) * den.signum();
+    Rational {
+      num: num / g,
+      den: den / g,
+    }
+  }
+
+  pub fn zero() -> Rational {
+    Rational::new(0, 1)
+  }
+
+  pub fn one() -> Rational {
+    Rational::new(1, 1)
+  }
+
+  pub fn is_zero(&self) -> bool {
+    self.num == 0
+  }
+
+  // convert through the shortest decimal representation so `0.1` becomes `1/10`
+  pub fn from_f64(n: f64) -> Option<Rational> {
+    if !n.is_finite() {
+      return None;
+    }
+    let s = n.to_string();
+    let (int, frac) = s.split_once('.').unwrap_or((&s, ""));
+    let den = 10i128.checked_pow(frac.len() as u32)?;
+    let num = (int.to_string() + frac).parse::<i128>().ok()?;
+    Some(Rational::new(num, den))
+  }
+
+  pub fn recip(&self) -> Rational {
+    Rational::new(self.den, self.num)
+  }
+
+  pub fn pow(&self, exp: i32) -> Option<Rational> {
+    let base = if exp < 0 { self.recip() } else { *self };
+    let mut out = Rational::one();
+    for _ in 0..exp.unsigned_abs() {
+      out = out.checked_mul(base)?;
+    }
+    Some(out)
+  }
+
+  // the arithmetic is checked, and cancels common factors before multiplying so the numbers only
+  // grow as much as the result needs: sums are taken over the lowest common denominator
+  pub fn checked_add(self, other: Rational) -> Option<Rational> {
+    let g = gcd(self.den, other.den);
+    Some(Rational::new(
+      self
+        .num
+        .checked_mul(other.den / g)?
+        .checked_add(other.num.checked_mul(self.den / g)?)?,
+      (self.den / g).checked_mul(other.den)?,
+    ))
+  }
+
+  pub fn checked_sub(self, other: Rational) -> Option<Rational> {
+    self.checked_add(-other)
+  }
+
+  // each numerator is divided by what it shares with the other denominator first
+  pub fn checked_mul(self, other: Rational) -> Option<Rational> {
+    let (a, b) = (gcd(self.num, other.den), gcd(other.num, self.den));
+    Some(Rational::new(
+      (self.num / a).checked_mul(other.num / b)?,
+      (self.den / b).checked_mul(other.den / a)?,
+    ))
+  }
+
+  pub fn checked_div(self, other: Rational) -> Option<Rational> {
+    self.checked_mul(other.recip())
+  }
+}
+
+fn overflow() -> String {
+  "Coefficients are too large to solve exactly.".to_string()
+}
+
+impl std::fmt::Display for Rational {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    if self.den == 1 {
+      write!(f, "{}", self.num)
+    } else {
+      write!(f, "{}/{}", self.num, self.den)
+    }
+  }
+}
+
+// written as a string such as "3/2" so no precision is lost
+impl Serialize for Rational {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&self.to_string())
+  }
+}
+
+impl std::ops::Neg for Rational {
+  type Output = Rational;
+  fn neg(self) -> Rational {
+    Rational::new(-self.num, self.den)
+  }
+}
+
+// a linear combination of variables plus a constant
+#[derive(Debug, Clone, PartialEq)]
+pub struct Linear {
+  pub coefficients: BTreeMap<String, Rational>,
+  pub constant: Rational,
+}
+
+impl Linear {
+  fn constant(n: Rational) -> Linear {
+    Linear {
+      coefficients: BTreeMap::new(),
+      constant: n,
+    }
+  }
+
+  fn variable(v: String) -> Linear {
+    Linear {
+      coefficients: BTreeMap::from([(v, Rational::one())]),
+      constant: Rational::zero(),
+    }
+  }
+
+  fn as_constant(&self) -> Option<Rational> {
+    if self.coefficients.is_empty() {
+      Some(self.constant)
+    } else {
+      None
+    }
+  }
+
+  fn add(mut self, other: Linear, sign: Rational) -> Result<Linear, String> {
+    for (v, n) in other.coefficients {
+      let c = self
+        .coefficients
+        .get(&v)
+        .unwrap_or(&Rational::zero())
+        .checked_add(sign.checked_mul(n).ok_or_else(overflow)?)
+        .ok_or_else(overflow)?;
+      if c.is_zero() {
+        self.coefficients.remove(&v);
+      } else {
+        self.coefficients.insert(v, c);
+      }
+    }
+    self.constant = sign
+      .checked_mul(other.constant)
+      .and_then(|n| self.constant.checked_add(n))
+      .ok_or_else(overflow)?;
+    Ok(self)
+  }
+
+  fn scale(mut self, n: Rational) -> Result<Linear, String> {
+    if n.is_zero() {
+      return Ok(Linear::constant(Rational::zero()));
+    }
+    for c in self.coefficients.values_mut() {
+      *c = c.checked_mul(n).ok_or_else(overflow)?;
+    }
+    self.constant = self.constant.checked_mul(n).ok_or_else(overflow)?;
+    Ok(self)
+  }
+
+  // read a linear combination out of an expression, failing on anything non-linear
+  pub fn from(ast: AST) -> Result<Linear, String> {
+    match ast.clone() {
+      AST::Number(n) => Rational::from_f64(n)
+        .map(Linear::constant)
+        .ok_or(format!("Cannot represent {} exactly.", n)),
+      AST::Variable(v) => Ok(Linear::variable(v)),
+      AST::Unary(u) => match u.sign {
+        Sign::Add => Linear::from(*u.unary),
+        Sign::Sub => Linear::from(*u.unary)?.scale(-Rational::one()),
+        _ => Err(format!("Not linear: {:?}", ast)),
+      },
+      AST::Expr(e) if e.sign == Sign::Add || e.sign == Sign::Sub => {
+        let sign = if e.sign == Sign::Add {
+          Rational::one()
+        } else {
+          -Rational::one()
+        };
+        let mut out = Linear::from(*e.expr[0].clone())?;
+        for i in e.expr.into_iter().skip(1) {
+          out = out.add(Linear::from(*i)?, sign)?;
+        }
+        Ok(out)
+      }
+      AST::Term(t) if t.sign == Sign::Mul || t.sign == Sign::Div => {
+        let mut out = Linear::from(*t.term[0].clone())?;
+        for i in t.term.into_iter().skip(1) {
+          let i = Linear::from(*i)?;
+          out = match (t.sign.clone(), out.as_constant(), i.as_constant()) {
+            (Sign::Mul, _, Some(n)) => out.scale(n)?,
+            (Sign::Mul, Some(n), _) => i.scale(n)?,
+            (Sign::Div, _, Some(n)) if !n.is_zero() => out.scale(n.recip())?,
+            (Sign::Div, _, Some(_)) => return Err("Division by zero.".to_string()),
+            _ => return Err(format!("Not linear: {:?}", ast)),
+          };
+        }
+        Ok(out)
+      }
+      AST::Index(i) => {
+        let base = Linear::from(*i.index.0)?;
+        match (base.as_constant(), Linear::from(*i.index.1)?.as_constant()) {
+          (_, Some(n)) if n == Rational::one() => Ok(base),
+          (Some(b), Some(n)) if n.den == 1 && n.num.abs() <= 64 && !(b.is_zero() && n.num < 0) => {
+            Ok(Linear::constant(b.pow(n.num as i32).ok_or_else(overflow)?))
+          }
+          _ => Err(format!("Not linear: {:?}", ast)),
+        }
+      }
+      AST::Call(_) => match expand(ast.clone()) {
+        AST::Number(n) => Linear::from(AST::Number(n)),
+        _ => Err(format!("Not linear: {:?}", ast)),
+      },
+      _ => Err(format!("Not linear: {:?}", ast)),
+    }
+  }
+}
+
+impl std::fmt::Display for Linear {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    let mut first = true;
+    for (v, n) in &self.coefficients {
+      if first {
+        if *n == -Rational::one() {
+          write!(f, "-")?;
+        } else if *n != Rational::one() {
+          write!(f, "{}", n)?;
+        }
+      } else if n.num < 0 {
+        write!(f, " - ")?;
+        if *n != -Rational::one() {
+          write!(f, "{}", -*n)?;
+        }
+      } else {
+        write!(f, " + ")?;
+        if *n != Rational::one() {
+          write!(f, "{}", n)?;
+        }
+      }
+      write!(f, "{}", v)?;
+      first = false;
+    }
+    if first {
+      write!(f, "{}", self.constant)
+    } else if self.constant.num < 0 {
+      write!(f, " - {}", -self.constant)
+    } else if !self.constant.is_zero() {
+      write!(f, " + {}", self.constant)
+    } else {
+      Ok(())
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Solution {
+  Unique(BTreeMap<String, Rational>),
+  // each pivot variable written in terms of the free variables
+  Infinite {
+    free: Vec<String>,
+    pivots: Vec<(String, Linear)>,
+  },
+  None,
+}
+
+impl Solution {
+  // exact values as strings, alongside their nearest floats
+  pub fn json(&self) -> serde_json::Value {
+    let value =
+      |n: &Rational| serde_json::json!({ "exact": n, "value": n.num as f64 / n.den as f64 });
+    match self {
+      Solution::Unique(values) => serde_json::json!({
+        "kind": "unique",
+        "values": values
+          .iter()
+          .map(|(v, n)| (v.clone(), value(n)))
+          .collect::<serde_json::Map<String, serde_json::Value>>(),
+      }),
+      Solution::Infinite { free, pivots } => serde_json::json!({
+        "kind": "infinite",
+        "free": free,
+        "pivots": pivots
+          .iter()
+          .map(|(v, l)| (v.clone(), serde_json::json!(l.to_string())))
+          .collect::<serde_json::Map<String, serde_json::Value>>(),
+      }),
+      Solution::None => serde_json::json!({ "kind": "none" }),
+    }
+  }
+}
+
+impl std::fmt::Display for Solution {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      Solution::Unique(values) => write!(
+        f,
+        "{}",
+        values
+          .iter()
+          .map(|(v, n)| format!("{} = {}", v, n))
+          .collect::<Vec<String>>()
+          .join(", ")
+      ),
+      Solution::Infinite { free, pivots } => {
+        write!(f, "infinitely many, free: {}", free.join(", "))?;
+        for (v, l) in pivots {
+          write!(f, "\n  {} = {}", v, l)?;
+        }
+        Ok(())
+      }
+      Solution::None => write!(f, "no solution"),
+    }
+  }
+}
+
+// a system of linear equations, each row reads `coefficients . variables = constant`
+#[derive(Debug, Clone)]
+pub struct LinearSystem {
+  pub variables: Vec<String>,
+  pub rows: Vec<(Vec<Rational>, Rational)>,
+}
+
+impl LinearSystem {
+  pub fn from(equations: Vec<AST>) -> Result<LinearSystem, String> {
+    let mut forms: Vec<Linear> = Vec::new();
+    for equation in equations {
+      forms.push(match equation {
+        AST::Statement(s) => LinearSystem::one_side(*s.statement)?,
+        other => LinearSystem::one_side(other)?,
+      });
+    }
+    let mut variables: Vec<String> = forms
+      .iter()
+      .flat_map(|l| l.coefficients.keys().cloned())
+      .collect();
+    variables.sort();
+    variables.dedup();
+    let rows = forms
+      .iter()
+      .map(|l| {
+        (
+          variables
+            .iter()
+            .map(|v| *l.coefficients.get(v).unwrap_or(&Rational::zero()))
+            .collect(),
+          -l.constant,
+        )
+      })
+      .collect();
+    Ok(LinearSystem { variables, rows })
+  }
+
+  // move everything in `a = b` to the left hand side
+  fn one_side(ast: AST) -> Result<Linear, String> {
+    match ast {
+      AST::Identity(i) if i.identity.len() == 2 => Linear::from(*i.identity[0].clone())?
+        .add(Linear::from(*i.identity[1].clone())?, -Rational::one()),
+      _ => Err("Expected an equation of the form a = b.".to_string()),
+    }
+  }
+
+  // gauss-jordan elimination to reduced row echelon form, failing if the fractions overflow
+  pub fn solve(&self) -> Result<Solution, String> {
+    let mut rows = self.rows.clone();
+    let mut pivots: Vec<usize> = Vec::new();
+    let mut r = 0;
+    for c in 0..self.variables.len() {
+      let p = match (r..rows.len()).find(|&i| !rows[i].0[c].is_zero()) {
+        Some(p) => p,
+        None => continue,
+      };
+      rows.swap(r, p);
+      let n = rows[r].0[c];
+      for x in rows[r].0.iter_mut() {
+        *x = x.checked_div(n).ok_or_else(overflow)?;
+      }
+      rows[r].1 = rows[r].1.checked_div(n).ok_or_else(overflow)?;
+      let pivot = rows[r].clone();
+      for (i, row) in rows.iter_mut().enumerate() {
+        let m = row.0[c];
+        if i != r && !m.is_zero() {
+          let less = |x: Rational, y: Rational| {
+            m.checked_mul(y)
+              .and_then(|y| x.checked_sub(y))
+              .ok_or_else(overflow)
+          };
+          for (x, &y) in row.0.iter_mut().zip(pivot.0.iter()) {
+            *x = less(*x, y)?;
+          }
+          row.1 = less(row.1, pivot.1)?;
+        }
+      }
+      pivots.push(c);
+      r += 1;
+    }
+
+    Ok(if rows[r..].iter().any(|(_, n)| !n.is_zero()) {
+      Solution::None
+    } else if pivots.len() == self.variables.len() {
+      Solution::Unique(
+        pivots
+          .iter()
+          .enumerate()
+          .map(|(i, &c)| (self.variables[c].clone(), rows[i].1))
+          .collect(),
+      )
+    } else {
+      let free = (0..self.variables.len())
+        .filter(|c| !pivots.contains(c))
+        .collect::<Vec<usize>>();
+      Solution::Infinite {
+        free: free.iter().map(|&c| self.variables[c].clone()).collect(),
+        pivots: pivots
+          .iter()
+          .enumerate()
+          .map(|(i, &c)| {
+            (
+              self.variables[c].clone(),
+              Linear {
+                coefficients: free
+                  .iter()
+                  .filter(|&&f| !rows[i].0[f].is_zero())
+                  .map(|&f| (self.variables[f].clone(), -rows[i].0[f]))
+                  .collect(),
+                constant: rows[i].1,
+              },
+            )
+          })
+          .collect(),
+      }
+    })
+  }
+}
+
+impl std::fmt::Display for LinearSystem {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    for (i, (row, n)) in self.rows.iter().enumerate() {
+      if i != 0 {
+        writeln!(f)?;
+      }
+      write!(
+        f,
+        "  [{} | {}]",
+        row
+          .iter()
+          .map(|x| x.to_string())
+          .collect::<Vec<String>>()
+          .join(", "),
+        n
+      )?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::syntax::parser::Parser;
+
+  fn system(equations: &[&str]) -> LinearSystem {
+    LinearSystem::from(
+      equations
+        .iter()
+        .map(|e| *Parser::new(e.to_string() + ";").parse().unwrap()[0].clone())
+        .collect(),
+    )
+    .unwrap()
+  }
+
+  // three equations with exactly one solution
+  #[test]
+  fn unique() {
+    assert_eq!(
+      system(&["x + y + z = 6", "2x - y + z = 3", "x + 2y - z = 2"])
+        .solve()
+        .unwrap(),
+      Solution::Unique(BTreeMap::from([
+        ("x".to_string(), Rational::new(1, 1)),
+        ("y".to_string(), Rational::new(2, 1)),
+        ("z".to_string(), Rational::new(3, 1)),
+      ]))
+    );
+  }
+
+  // decimals are kept exact rather than rounded through floats
+  #[test]
+  fn exact() {
+    assert_eq!(
+      system(&["0.1x + y = 0.3", "x / 2 = y"]).solve().unwrap(),
+      Solution::Unique(BTreeMap::from([
+        ("x".to_string(), Rational::new(1, 2)),
+        ("y".to_string(), Rational::new(1, 4)),
+      ]))
+    );
+  }
+
+  // dependent equations leave a free variable
+  #[test]
+  fn infinite() {
+    assert_eq!(
+      system(&["x + y = 1", "2x + 2y = 2"])
+        .solve()
+        .unwrap()
+        .to_string(),
+      "infinitely many, free: y\n  x = -y + 1"
+    );
+  }
+
+  // parallel lines never meet
+  #[test]
+  fn inconsistent() {
+    assert_eq!(
+      system(&["x + y = 1", "x + y = 2"]).solve().unwrap(),
+      Solution::None
+    );
+  }
+
+  // anything with a product of variables is rejected
+  #[test]
+  fn non_linear() {
+    assert!(
+      LinearSystem::from(vec![*Parser::new("y = x^2;".to_string()).parse().unwrap()
+        [0]
+        .clone()])
+      .is_err()
+    );
+  }
+
+  // cancelling as it goes keeps a system of 9 digit decimals exact, while decimals long enough
+  // to overflow an i128 anyway are reported rather than wrapping
+  #[test]
+  fn overflow() {
+    let solution = system(&[
+      "0.123456789x + 0.987654321y + 0.3z = 0.3",
+      "0.314159265x - 0.271828182y + 0.7z= 0.7",
+      "0.161803398x + 0.141421356y + 0.1z = 1",
+    ])
+    .solve()
+    .unwrap();
+    assert_eq!(
+      solution.to_string(),
+      "x = 257635493100000000/33993320704259029, y = 2609342400000000/33993320704259029, \
+       z = -80619942652028192/33993320704259029"
+    );
+    assert!(system(&[
+      "0.1234567891234x + 0.9876543219876y + 0.3z = 0.3",
+      "0.3141592653589x - 0.2718281828459y + 0.7z= 0.7",
+      "0.1618033988749x + 0.1414213562373y + 0.1z = 1",
+    ])
+    .solve()
+    .is_err());
+  }
+
+  // json keeps each value exact as a string, with its float alongside
+  #[test]
+  fn json() {
+    assert_eq!(
+      system(&["2x = 1", "y = 3"]).solve().unwrap().json(),
+      serde_json::json!({
+        "kind": "unique",
+        "values": {
+          "x": { "exact": "1/2", "value": 0.5 },
+          "y": { "exact": "3", "value": 3.0 },
+        },
+      })
+    );
+  }
+}
@@ -1,3 +1,4 @@
+pub mod linear;
 pub mod quadratic;
 
 mod expand;
@@ -6,8 +7,35 @@ pub use expand::expand;
 mod pretty;
 pub use pretty::pretty;
 
+mod notation;
+pub use notation::{to_latex, to_mathml};
+
 mod draw;
-pub use draw::draw;
+pub use draw::{draw, Dash, Figure, Format, Panel, Style, Theme};
 
 mod eval;
-pub use eval::eval;
+pub use eval::{constant, eval};
+
+mod round;
+pub use round::round;
+
+mod points;
+pub use points::{intercepts, intersections, turning_points, unique};
+
+mod fit;
+pub use fit::{fit, Fit, Model};
+
+mod tangent;
+pub use tangent::{tangents, vertical, Tangent};
+
+mod steps;
+pub use steps::{steps, Step};
+
+mod substitute;
+pub use substitute::{substitute, variables};
+
+mod eliminate;
+pub use eliminate::{eliminate, Elimination};
+
+mod verify;
+pub use verify::{verify, Check};
@@ -0,0 +1,460 @@
+use crate::syntax::parser::{Relation, Sign, AST};
+
+// how tightly each node binds, a child binding more loosely than its parent needs brackets
+const EQUALS: u8 = 0;
+const COMPARE: u8 = 1;
+const SUM: u8 = 2;
+const PRODUCT: u8 = 3;
+const POWER: u8 = 4;
+const ATOM: u8 = 5;
+
+// functions with their own command in latex, the rest are written with \operatorname
+const LATEX_FUNCTIONS: [&str; 8] = ["sin", "cos", "tan", "log", "ln", "exp", "min", "max"];
+
+// functions written as brackets around their argument
+const FENCES: [&str; 3] = ["abs", "floor", "ceil"];
+
+// the pieces of a typeset expression, the layout is shared by every notation
+trait Notation {
+  fn number(&self, n: f64) -> String;
+  fn variable(&self, v: &str) -> String;
+  // an operator between two operands, or before one when unary
+  fn operator(&self, sign: &Sign, unary: bool) -> String;
+  // two factors written side by side, such as `2x`
+  fn juxtapose(&self) -> String;
+  fn equals(&self) -> String;
+  fn brackets(&self, inner: String) -> String;
+  fn row(&self, parts: Vec<String>) -> String;
+  fn fraction(&self, numerator: String, denominator: String) -> String;
+  fn power(&self, base: String, exponent: String) -> String;
+  fn sqrt(&self, inner: String) -> String;
+  fn function(&self, name: &str, args: Vec<String>) -> String;
+  // `|x|`, `⌊x⌋` and `⌈x⌉`, named after the function they stand for
+  fn fence(&self, name: &str, inner: String) -> String;
+  fn factorial(&self, inner: String) -> String;
+  fn relation(&self, relation: &Relation) -> String;
+  // the rows of a piecewise, each a value and the condition it holds under
+  fn cases(&self, cases: Vec<(String, String)>, otherwise: Option<String>) -> String;
+  // a list of `(x, y)` pairs
+  fn points(&self, points: Vec<(String, String)>) -> String;
+}
+
+fn precedence(ast: &AST) -> u8 {
+  match ast {
+    AST::Identity(_) => EQUALS,
+    AST::Compare(_) => COMPARE,
+    AST::Expr(e) if e.expr.len() > 1 => SUM,
+    AST::Expr(e) if e.expr.len() == 1 => precedence(&e.expr[0]),
+    AST::Term(t) if t.term.len() == 1 => precedence(&t.term[0]),
+    // a fraction is as tight as a product, it only needs brackets when raised to a power
+    AST::Term(_) => PRODUCT,
+    AST::Unary(_) => SUM,
+    AST::Number(n) if *n < 0f64 => SUM,
+    AST::Index(_) => POWER,
+    AST::Statement(s) => precedence(&s.statement),
+    _ => ATOM,
+  }
+}
+
+// whether a factor starts with something that cannot follow another factor directly
+fn starts_with_number(ast: &AST) -> bool {
+  match ast {
+    // a negative number is bracketed, so it can follow directly
+    AST::Number(n) => *n >= 0f64,
+    AST::Index(i) => starts_with_number(&i.index.0),
+    AST::Term(t) if t.sign == Sign::Mul => starts_with_number(&t.term[0]),
+    AST::Expr(e) if e.expr.len() == 1 => starts_with_number(&e.expr[0]),
+    _ => false,
+  }
+}
+
+fn render(ast: &AST, n: &dyn Notation) -> String {
+  // bracket a child that binds more loosely than min
+  let child = |ast: &AST, min: u8| {
+    if precedence(ast) < min {
+      n.brackets(render(ast, n))
+    } else {
+      render(ast, n)
+    }
+  };
+  match ast {
+    AST::Number(x) => n.number(*x),
+    AST::Variable(v) => n.variable(v),
+    AST::Expr(e) if e.expr.is_empty() => n.number(0f64),
+    AST::Expr(e) => {
+      let mut parts = vec![child(&e.expr[0], SUM)];
+      for c in e.expr[1..].iter() {
+        parts.push(n.operator(&e.sign, false));
+        // `a - (b + c)` keeps its brackets but `a + (b + c)` does not need them
+        let associative =
+          e.sign == Sign::Add && matches!(**c, AST::Expr(ref i) if i.sign == Sign::Add);
+        parts.push(if associative {
+          render(c, n)
+        } else {
+          child(c, PRODUCT)
+        });
+      }
+      n.row(parts)
+    }
+    AST::Term(t) if t.term.len() == 1 => render(&t.term[0], n),
+    AST::Term(t) if t.sign == Sign::Div => {
+      // `a / b / c` is a single fraction over `b c`
+      let denominator = match t.term.len() {
+        2 => render(&t.term[1], n),
+        _ => render(
+          &AST::Term(crate::syntax::parser::Term {
+            sign: Sign::Mul,
+            term: t.term[1..].to_vec(),
+          }),
+          n,
+        ),
+      };
+      n.fraction(render(&t.term[0], n), denominator)
+    }
+    AST::Term(t) => {
+      let mut parts = vec![child(&t.term[0], PRODUCT)];
+      for c in t.term[1..].iter() {
+        let factor = child(c, PRODUCT);
+        if starts_with_number(c) || t.sign != Sign::Mul {
+          parts.push(n.operator(&t.sign, false));
+        } else {
+          parts.push(n.juxtapose());
+        }
+        parts.push(factor);
+      }
+      n.row(parts)
+    }
+    // powers are right associative, so only the base can need brackets
+    AST::Index(i) => n.power(child(&i.index.0, ATOM), render(&i.index.1, n)),
+    AST::Unary(u) => n.row(vec![n.operator(&u.sign, true), child(&u.unary, PRODUCT)]),
+    AST::Call(c) if c.name == "sqrt" && c.call.len() == 1 => n.sqrt(render(&c.call[0], n)),
+    AST::Call(c) if FENCES.contains(&c.name.as_str()) && c.call.len() == 1 => {
+      n.fence(&c.name, render(&c.call[0], n))
+    }
+    AST::Call(c) if c.name == "factorial" && c.call.len() == 1 => {
+      n.factorial(child(&c.call[0], ATOM))
+    }
+    AST::Call(c) => n.function(&c.name, c.call.iter().map(|a| render(a, n)).collect()),
+    AST::Identity(i) => {
+      let mut parts = vec![render(&i.identity[0], n)];
+      for side in i.identity[1..].iter() {
+        parts.push(n.equals());
+        parts.push(render(side, n));
+      }
+      n.row(parts)
+    }
+    AST::Compare(c) => n.row(vec![
+      render(&c.compare.0, n),
+      n.relation(&c.relation),
+      render(&c.compare.1, n),
+    ]),
+    AST::Piecewise(p) => n.cases(
+      p.piecewise
+        .iter()
+        .map(|(condition, value)| (render(value, n), render(condition, n)))
+        .collect(),
+      p.otherwise.as_ref().map(|o| render(o, n)),
+    ),
+    AST::Points(p) => n.points(
+      p.points
+        .iter()
+        .map(|(x, y)| (render(x, n), render(y, n)))
+        .collect(),
+    ),
+    AST::Statement(s) => render(&s.statement, n),
+  }
+}
+
+struct Latex;
+
+impl Notation for Latex {
+  fn number(&self, n: f64) -> String {
+    n.to_string()
+  }
+
+  fn variable(&self, v: &str) -> String {
+    if v.chars().count() == 1 {
+      v.to_string()
+    } else {
+      format!("\\mathrm{{{}}}", v)
+    }
+  }
+
+  fn operator(&self, sign: &Sign, unary: bool) -> String {
+    let op = match sign {
+      Sign::Add => "+",
+      Sign::Sub => "-",
+      Sign::AddSub => "\\pm",
+      Sign::Mul => "\\cdot",
+      Sign::Div => "/",
+      Sign::Pow => "^",
+      Sign::Mod => "\\bmod",
+    };
+    if unary {
+      match sign {
+        Sign::AddSub => op.to_string() + " ",
+        _ => op.to_string(),
+      }
+    } else {
+      format!(" {} ", op)
+    }
+  }
+
+  fn juxtapose(&self) -> String {
+    String::new()
+  }
+
+  fn equals(&self) -> String {
+    " = ".to_string()
+  }
+
+  fn brackets(&self, inner: String) -> String {
+    format!("\\left({}\\right)", inner)
+  }
+
+  fn row(&self, parts: Vec<String>) -> String {
+    parts.concat()
+  }
+
+  fn fraction(&self, numerator: String, denominator: String) -> String {
+    format!("\\frac{{{}}}{{{}}}", numerator, denominator)
+  }
+
+  fn power(&self, base: String, exponent: String) -> String {
+    format!("{}^{{{}}}", base, exponent)
+  }
+
+  fn sqrt(&self, inner: String) -> String {
+    format!("\\sqrt{{{}}}", inner)
+  }
+
+  fn function(&self, name: &str, args: Vec<String>) -> String {
+    let name = if LATEX_FUNCTIONS.contains(&name) {
+      format!("\\{}", name)
+    } else {
+      format!("\\operatorname{{{}}}", name)
+    };
+    name + &self.brackets(args.join(", "))
+  }
+
+  fn fence(&self, name: &str, inner: String) -> String {
+    let (open, close) = match name {
+      "floor" => ("\\lfloor", "\\rfloor"),
+      "ceil" => ("\\lceil", "\\rceil"),
+      _ => ("|", "|"),
+    };
+    format!("\\left{} {} \\right{}", open, inner, close)
+  }
+
+  fn factorial(&self, inner: String) -> String {
+    inner + "!"
+  }
+
+  fn relation(&self, relation: &Relation) -> String {
+    let op = match relation {
+      Relation::Less => "<",
+      Relation::Greater => ">",
+      Relation::LessEqual => "\\le",
+      Relation::GreaterEqual => "\\ge",
+    };
+    format!(" {} ", op)
+  }
+
+  fn cases(&self, cases: Vec<(String, String)>, otherwise: Option<String>) -> String {
+    let mut rows: Vec<String> = cases
+      .into_iter()
+      .map(|(value, condition)| format!("{} & {}", value, condition))
+      .collect();
+    rows.extend(otherwise.map(|o| format!("{} & \\text{{otherwise}}", o)));
+    format!("\\begin{{cases}} {} \\end{{cases}}", rows.join(" \\\\ "))
+  }
+
+  fn points(&self, points: Vec<(String, String)>) -> String {
+    points
+      .into_iter()
+      .map(|(x, y)| self.brackets(format!("{}, {}", x, y)))
+      .collect::<Vec<String>>()
+      .join(", ")
+  }
+}
+
+struct MathMl;
+
+impl Notation for MathMl {
+  fn number(&self, n: f64) -> String {
+    if n < 0f64 {
+      self.row(vec![self.operator(&Sign::Sub, true), self.number(-n)])
+    } else {
+      format!("<mn>{}</mn>", n)
+    }
+  }
+
+  fn variable(&self, v: &str) -> String {
+    format!("<mi>{}</mi>", v)
+  }
+
+  fn operator(&self, sign: &Sign, _unary: bool) -> String {
+    let op = match sign {
+      Sign::Add => "+",
+      Sign::Sub => "&#x2212;",
+      Sign::AddSub => "&#xB1;",
+      Sign::Mul => "&#x22C5;",
+      Sign::Div => "/",
+      Sign::Pow => "^",
+      Sign::Mod => "mod",
+    };
+    format!("<mo>{}</mo>", op)
+  }
+
+  // an invisible times, so readers still know the factors are multiplied
+  fn juxtapose(&self) -> String {
+    "<mo>&#x2062;</mo>".to_string()
+  }
+
+  fn equals(&self) -> String {
+    "<mo>=</mo>".to_string()
+  }
+
+  fn brackets(&self, inner: String) -> String {
+    format!("<mrow><mo>(</mo>{}<mo>)</mo></mrow>", inner)
+  }
+
+  fn row(&self, parts: Vec<String>) -> String {
+    format!("<mrow>{}</mrow>", parts.concat())
+  }
+
+  fn fraction(&self, numerator: String, denominator: String) -> String {
+    format!("<mfrac>{}{}</mfrac>", numerator, denominator)
+  }
+
+  fn power(&self, base: String, exponent: String) -> String {
+    format!("<msup>{}{}</msup>", base, exponent)
+  }
+
+  fn sqrt(&self, inner: String) -> String {
+    format!("<msqrt>{}</msqrt>", inner)
+  }
+
+  fn function(&self, name: &str, args: Vec<String>) -> String {
+    self.row(vec![
+      format!("<mi>{}</mi>", name),
+      "<mo>&#x2061;</mo>".to_string(),
+      self.brackets(args.join("<mo>,</mo>")),
+    ])
+  }
+
+  fn fence(&self, name: &str, inner: String) -> String {
+    let (open, close) = match name {
+      "floor" => ("&#x230A;", "&#x230B;"),
+      "ceil" => ("&#x2308;", "&#x2309;"),
+      _ => ("|", "|"),
+    };
+    format!("<mrow><mo>{}</mo>{}<mo>{}</mo></mrow>", open, inner, close)
+  }
+
+  fn factorial(&self, inner: String) -> String {
+    self.row(vec![inner, "<mo>!</mo>".to_string()])
+  }
+
+  fn relation(&self, relation: &Relation) -> String {
+    let op = match relation {
+      Relation::Less => "&lt;",
+      Relation::Greater => "&gt;",
+      Relation::LessEqual => "&#x2264;",
+      Relation::GreaterEqual => "&#x2265;",
+    };
+    format!("<mo>{}</mo>", op)
+  }
+
+  fn cases(&self, cases: Vec<(String, String)>, otherwise: Option<String>) -> String {
+    let mut rows: Vec<String> = cases
+      .into_iter()
+      .map(|(value, condition)| format!("<mtr><mtd>{}</mtd><mtd>{}</mtd></mtr>", value, condition))
+      .collect();
+    rows.extend(otherwise.map(|o| {
+      format!(
+        "<mtr><mtd>{}</mtd><mtd><mtext>otherwise</mtext></mtd></mtr>",
+        o
+      )
+    }));
+    format!("<mrow><mo>{{</mo><mtable>{}</mtable></mrow>", rows.concat())
+  }
+
+  fn points(&self, points: Vec<(String, String)>) -> String {
+    self.row(vec![points
+      .into_iter()
+      .map(|(x, y)| self.brackets(format!("{}<mo>,</mo>{}", x, y)))
+      .collect::<Vec<String>>()
+      .join("<mo>,</mo>")])
+  }
+}
+
+pub fn to_latex(ast: &AST) -> String {
+  render(ast, &Latex)
+}
+
+pub fn to_mathml(ast: &AST) -> String {
+  format!(
+    "<math xmlns=\"http://www.w3.org/1998/Math/MathML\">{}</math>",
+    render(ast, &MathMl)
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::syntax::parser::Parser;
+
+  fn latex(input: &str) -> String {
+    to_latex(&Parser::new(input.to_string() + ";").parse().unwrap()[0])
+  }
+
+  // only the brackets the precedence needs are kept
+  #[test]
+  fn brackets() {
+    assert_eq!(latex("y = 2y + 1"), "y = 2y + 1");
+    assert_eq!(
+      latex("y = 3(x + 1)^2 - (x - 2)"),
+      "y = 3\\left(x + 1\\right)^{2} - \\left(x - 2\\right)"
+    );
+    assert_eq!(latex("y = x * 5 * -x"), "y = x \\cdot 5\\left(-x\\right)");
+  }
+
+  // division, roots and ± are typeset rather than written inline
+  #[test]
+  fn layout() {
+    assert_eq!(latex("y = (x + 1) / 2"), "y = \\frac{x + 1}{2}");
+    assert_eq!(latex("y = 2^x^2"), "y = 2^{x^{2}}");
+    let solved = crate::functions::quadratic::Quadratic::new(
+      AST::Number(1f64),
+      AST::Number(0f64),
+      AST::Number(-4f64),
+      "x".to_string(),
+    )
+    .solve()
+    .unwrap();
+    assert_eq!(
+      to_latex(&solved),
+      "x = \\frac{-0 \\pm \\sqrt{0^{2} - 4 \\cdot 1\\left(-4\\right)}}{2 \\cdot 1}"
+    );
+  }
+
+  // fences, factorials and cases are typeset with their own symbols
+  #[test]
+  fn piecewise() {
+    assert_eq!(
+      latex("y = {x < 0: |x|; floor(x)! mod 2}"),
+      "y = \\begin{cases} \\left| x \\right| & x < 0 \\\\ \\left\\lfloor x \\right\\rfloor! \\bmod 2 & \\text{otherwise} \\end{cases}"
+    );
+  }
+
+  // mathml uses the same layout
+  #[test]
+  fn mathml() {
+    let ast = Parser::new("y = x^2 / 2;".to_string()).parse().unwrap();
+    assert_eq!(
+      to_mathml(&ast[0]),
+      "<math xmlns=\"http://www.w3.org/1998/Math/MathML\"><mrow><mi>y</mi><mo>=</mo><mfrac><msup><mi>x</mi><mn>2</mn></msup><mn>2</mn></mfrac></mrow></math>"
+    );
+  }
+}
@@ -0,0 +1,218 @@
+use super::{eval, vertical};
+use crate::syntax::parser::AST;
+
+// number of samples taken across the range when looking for sign changes
+const SAMPLES: usize = 4000;
+// how close two points can be before they are treated as the same point
+const TOLERANCE: f64 = 1e-6;
+
+// evaluate every branch of a solved curve `y = f(x)` (a `±` gives two branches), none when the
+// curve has a name with no value
+pub fn branches(ast: &AST, x: f64) -> Vec<f64> {
+  match ast {
+    AST::Identity(identity) => eval(*identity.identity[1].clone(), x),
+    _ => eval(ast.clone(), x),
+  }
+  .unwrap_or_default()
+}
+
+// find every x in the range where f changes sign, refined by bisection
+fn roots(f: &dyn Fn(f64) -> f64, range: (f64, f64)) -> Vec<f64> {
+  let step = (range.1 - range.0) / SAMPLES as f64;
+  let mut out: Vec<f64> = Vec::new();
+  let mut a = range.0;
+  let mut fa = f(a);
+  let mut before = f64::NAN;
+  for i in 1..=SAMPLES {
+    let b = range.0 + step * i as f64;
+    let fb = f(b);
+    // only isolated zeros count, a curve lying along zero has no single root
+    if fa == 0f64 {
+      if before != 0f64 && fb != 0f64 {
+        out.push(a);
+      }
+    } else if fa * fb < 0f64 {
+      let (mut lo, mut hi, mut flo) = (a, b, fa);
+      for _ in 0..64 {
+        let mid = (lo + hi) / 2f64;
+        let fmid = f(mid);
+        if fmid == 0f64 {
+          (lo, hi) = (mid, mid);
+          break;
+        } else if flo * fmid < 0f64 {
+          hi = mid;
+        } else {
+          (lo, flo) = (mid, fmid);
+        }
+      }
+      let x = (lo + hi) / 2f64;
+      // a sign change across an asymptote is not a root
+      if f(x).abs() < TOLERANCE.sqrt() {
+        out.push(x);
+      }
+    }
+    (before, a, fa) = (fa, b, fb);
+  }
+  if fa == 0f64 && before != 0f64 {
+    out.push(a);
+  }
+  out
+}
+
+// drop repeated points, such as where three curves cross at once
+pub fn unique(points: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+  let mut out: Vec<(f64, f64)> = Vec::new();
+  for p in points {
+    if !out
+      .iter()
+      .any(|q| (p.0 - q.0).abs() < TOLERANCE && (p.1 - q.1).abs() < TOLERANCE)
+    {
+      // adding zero turns a negative zero into a positive one
+      out.push((p.0 + 0f64, p.1 + 0f64));
+    }
+  }
+  out
+}
+
+// the x of each vertical line `x = x0` that is in the range
+fn verticals(line: &AST, range: (f64, f64)) -> Vec<f64> {
+  branches(line, 0f64)
+    .into_iter()
+    .filter(|x| range.0 <= *x && *x <= range.1)
+    .collect()
+}
+
+// the points where a vertical line meets a curve, at whatever values the curve has there
+fn across(line: &AST, curve: &AST, range: (f64, f64)) -> Vec<(f64, f64)> {
+  let mut out: Vec<(f64, f64)> = Vec::new();
+  for x in verticals(line, range) {
+    for y in branches(curve, x) {
+      if y.is_finite() {
+        out.push((x, y));
+      }
+    }
+  }
+  unique(out)
+}
+
+// points where two solved curves cross. a vertical line is not a function of x, so it is met
+// directly rather than searched for, and never meets another vertical line
+pub fn intersections(a: &AST, b: &AST, range: (f64, f64)) -> Vec<(f64, f64)> {
+  match (vertical(a), vertical(b)) {
+    (true, true) => return Vec::new(),
+    (true, false) => return across(a, b, range),
+    (false, true) => return across(b, a, range),
+    (false, false) => {}
+  }
+  let mut out: Vec<(f64, f64)> = Vec::new();
+  for i in 0..branches(a, range.0).len() {
+    for j in 0..branches(b, range.0).len() {
+      let f = |x: f64| branches(a, x)[i] - branches(b, x)[j];
+      for x in roots(&f, range) {
+        out.push((x, branches(a, x)[i]));
+      }
+    }
+  }
+  unique(out)
+}
+
+// points where a solved curve crosses the x and y axes
+pub fn intercepts(ast: &AST, range: (f64, f64)) -> Vec<(f64, f64)> {
+  if vertical(ast) {
+    return unique(
+      verticals(ast, range)
+        .into_iter()
+        .map(|x| (x, 0f64))
+        .collect(),
+    );
+  }
+  let mut out: Vec<(f64, f64)> = Vec::new();
+  for i in 0..branches(ast, range.0).len() {
+    let f = |x: f64| branches(ast, x)[i];
+    for x in roots(&f, range) {
+      out.push((x, 0f64));
+    }
+    if range.0 <= 0f64 && 0f64 <= range.1 && f(0f64).is_finite() {
+      out.push((0f64, f(0f64)));
+    }
+  }
+  unique(out)
+}
+
+// local maxima and minima of a solved curve, where the gradient changes sign
+pub fn turning_points(ast: &AST, range: (f64, f64)) -> Vec<(f64, f64)> {
+  if vertical(ast) {
+    return Vec::new();
+  }
+  let h = (range.1 - range.0) / SAMPLES as f64 / 10f64;
+  let mut out: Vec<(f64, f64)> = Vec::new();
+  for i in 0..branches(ast, range.0).len() {
+    let f = |x: f64| branches(ast, x)[i];
+    let gradient = |x: f64| (f(x + h) - f(x - h)) / (2f64 * h);
+    for x in roots(&gradient, range) {
+      out.push((x, f(x)));
+    }
+  }
+  unique(out)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::syntax::parser::Parser;
+
+  // the equation of a statement, as a solved curve is given
+  fn curve(input: &str) -> AST {
+    match *Parser::new(input.to_string() + ";").parse().unwrap()[0].clone() {
+      AST::Statement(s) => *s.statement,
+      ast => ast,
+    }
+  }
+
+  fn close(a: Vec<(f64, f64)>, b: Vec<(f64, f64)>) -> bool {
+    a.len() == b.len()
+      && a
+        .iter()
+        .zip(b.iter())
+        .all(|(p, q)| (p.0 - q.0).abs() < 1e-6 && (p.1 - q.1).abs() < 1e-6)
+  }
+
+  // two lines cross once
+  #[test]
+  fn lines_intersect() {
+    let found = intersections(&curve("y = 3x"), &curve("y = 2x + 1"), (-20f64, 20f64));
+    assert!(close(found, vec![(1f64, 3f64)]));
+  }
+
+  // a line crosses both axes
+  #[test]
+  fn line_intercepts() {
+    let found = intercepts(&curve("y = 2x + 1"), (-20f64, 20f64));
+    assert!(close(found, vec![(-0.5f64, 0f64), (0f64, 1f64)]));
+  }
+
+  // a parabola turns at its vertex and a line never turns
+  #[test]
+  fn parabola_turns() {
+    let found = turning_points(&curve("y = (x + 1)^2 + 3"), (-20f64, 20f64));
+    assert!(close(found, vec![(-1f64, 3f64)]));
+    assert!(turning_points(&curve("y = 3"), (-20f64, 20f64)).is_empty());
+  }
+
+  // a vertical line meets a curve at the curve's value there, and crosses only the x axis
+  #[test]
+  fn vertical_lines() {
+    let range = (-20f64, 20f64);
+    let found = intersections(&curve("x = 3"), &curve("y = 2x"), range);
+    assert!(close(found, vec![(3f64, 6f64)]));
+    let found = intersections(&curve("y = x^2"), &curve("x = ±2"), range);
+    assert!(close(found, vec![(2f64, 4f64), (-2f64, 4f64)]));
+    assert!(intersections(&curve("x = 3"), &curve("x = 4"), range).is_empty());
+    assert!(intersections(&curve("x = 30"), &curve("y = x"), range).is_empty());
+    assert!(close(
+      intercepts(&curve("x = 3"), range),
+      vec![(3f64, 0f64)]
+    ));
+    assert!(turning_points(&curve("x = 3"), range).is_empty());
+  }
+}
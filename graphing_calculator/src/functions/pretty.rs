@@ -1,107 +1,330 @@
-use crate::syntax::parser::{Sign, AST};
+use crate::syntax::parser::{Relation, Sign, AST};
 
+// how tightly each node binds, following the levels of the parser
+const IDENTITY: u8 = 0;
+const COMPARE: u8 = 1;
+const EXPR: u8 = 2;
+const TERM: u8 = 3;
+const UNARY: u8 = 4;
+const INDEX: u8 = 5;
+const ATOM: u8 = 6;
+
+fn level(ast: &AST) -> u8 {
+  match ast {
+    AST::Identity(_) => IDENTITY,
+    AST::Compare(_) => COMPARE,
+    AST::Expr(e) if e.expr.len() == 1 => level(&e.expr[0]),
+    AST::Expr(e) if e.expr.len() > 1 => EXPR,
+    AST::Term(t) if t.term.len() == 1 => level(&t.term[0]),
+    AST::Term(_) => TERM,
+    // a negative number is written with a leading minus, like a unary
+    AST::Unary(_) => UNARY,
+    AST::Number(n) if n.is_sign_negative() => UNARY,
+    AST::Index(_) => INDEX,
+    AST::Statement(s) => level(&s.statement),
+    _ => ATOM,
+  }
+}
+
+fn symbol(sign: &Sign) -> &'static str {
+  match sign {
+    Sign::Add => "+",
+    Sign::Sub => "-",
+    Sign::AddSub => "±",
+    Sign::Mul => "*",
+    Sign::Div => "/",
+    Sign::Pow => "^",
+    Sign::Mod => "mod",
+  }
+}
+
+fn relation(relation: &Relation) -> &'static str {
+  match relation {
+    Relation::Less => "<",
+    Relation::Greater => ">",
+    Relation::LessEqual => "<=",
+    Relation::GreaterEqual => ">=",
+  }
+}
+
+// a child written in brackets when it binds more loosely than min
+fn child(ast: &AST, min: u8) -> String {
+  if level(ast) < min {
+    format!("({})", pretty(ast.clone()))
+  } else {
+    pretty(ast.clone())
+  }
+}
+
+// a factor that can follow a number directly, such as the `x` of `2x`. zero is never written
+// against a name starting with x, since `0xb` would read back as a hex literal
+fn juxtaposes(number: f64, ast: &AST) -> bool {
+  let name = match ast {
+    AST::Variable(v) => v,
+    AST::Index(i) => match i.index.0.as_ref() {
+      AST::Variable(v) => v,
+      _ => return false,
+    },
+    _ => return false,
+  };
+  number != 0f64 || !name.starts_with(['x', 'X'])
+}
+
+// write an expression with only the brackets needed to parse it back to the same tree
 pub fn pretty(ast: AST) -> String {
-  let mut out = String::new();
   match ast {
-    AST::Number(n) => out.push_str(&n.to_string()),
-    AST::Variable(v) => out.push_str(&v.to_string()),
+    AST::Number(n) => n.to_string(),
+    AST::Variable(v) => v,
+    AST::Expr(e) if e.expr.is_empty() => "0".to_string(),
     AST::Expr(e) => {
-      if e.expr.len() != 0 {
-        out.push_str("(");
-        let f = &pretty(*e.expr[0].clone());
-        if f.len() == 0 {
-          out.push_str("0");
-        } else {
-          out.push_str(f);
-        }
-        for i in 1..e.expr.len() {
-          out.push_str(" ");
-          out.push_str(match e.sign {
-            Sign::Add => "+",
-            Sign::Sub => "-",
-            Sign::AddSub => "±",
-            Sign::Mul => "*",
-            Sign::Div => "/",
-            Sign::Pow => "^",
-          });
-          out.push_str(" ");
-          out.push_str(&pretty(*e.expr[i].clone()));
-        }
-        out.push_str(")");
+      let mut out = child(&e.expr[0], EXPR);
+      for c in e.expr[1..].iter() {
+        out.push_str(&format!(" {} {}", symbol(&e.sign), child(c, TERM)));
       }
+      out
     }
     AST::Term(t) => {
-      out.push_str("(");
-      let f = &pretty(*t.term[0].clone());
-      if f.len() == 0 {
-        out.push_str("0");
-      } else {
-        out.push_str(f);
-      }
+      let mut out = child(&t.term[0], TERM);
       for i in 1..t.term.len() {
-        out.push_str(" ");
-        out.push_str(match t.sign {
-          Sign::Add => "+",
-          Sign::Sub => "-",
-          Sign::AddSub => "±",
-          Sign::Mul => "*",
-          Sign::Div => "/",
-          Sign::Pow => "^",
-        });
-        out.push_str(" ");
-        out.push_str(&pretty(*t.term[i].clone()));
+        let after_number = match *t.term[i - 1] {
+          AST::Number(n) if !n.is_sign_negative() => Some(n),
+          _ => None,
+        };
+        if t.sign == Sign::Mul && after_number.is_some_and(|n| juxtaposes(n, &t.term[i])) {
+          out.push_str(&pretty(*t.term[i].clone()));
+        } else {
+          out.push_str(&format!(
+            " {} {}",
+            symbol(&t.sign),
+            child(&t.term[i], UNARY)
+          ));
+        }
       }
-      out.push_str(")");
+      out
     }
-    AST::Index(i) => {
-      out.push_str("(");
-      out.push_str(&pretty(*i.index.0.clone()));
-      out.push_str(" ");
-      out.push_str(match i.sign {
-        Sign::Add => "+",
-        Sign::Sub => "-",
-        Sign::AddSub => "±",
-        Sign::Mul => "*",
-        Sign::Div => "/",
-        Sign::Pow => "^",
-      });
-      out.push_str(" ");
-      out.push_str(&pretty(*i.index.1.clone()));
-      out.push_str(")");
+    // powers are right associative, so `a^b^c` is `a^(b^c)`
+    AST::Index(i) => format!(
+      "{}{}{}",
+      child(&i.index.0, ATOM),
+      symbol(&i.sign),
+      child(&i.index.1, UNARY)
+    ),
+    // a unary inside a unary is bracketed so `+-` is not read as `±`
+    AST::Unary(u) => format!("{}{}", symbol(&u.sign), child(&u.unary, INDEX)),
+    AST::Identity(i) => i
+      .identity
+      .iter()
+      .map(|side| child(side, EXPR))
+      .collect::<Vec<String>>()
+      .join(" = "),
+    AST::Call(c) if c.name == "abs" && c.call.len() == 1 => {
+      format!("|{}|", pretty(*c.call[0].clone()))
     }
-    AST::Unary(u) => {
-      out.push_str("(");
-      out.push_str(match u.sign {
-        Sign::Add => "+",
-        Sign::Sub => "-",
-        Sign::AddSub => "±",
-        Sign::Mul => "*",
-        Sign::Div => "/",
-        Sign::Pow => "^",
-      });
-      out.push_str(&pretty(*u.unary.clone()));
-      out.push_str(")");
+    AST::Call(c) if c.name == "factorial" && c.call.len() == 1 => {
+      format!("{}!", child(&c.call[0], ATOM))
     }
-    AST::Identity(i) => {
-      out.push_str(&pretty(*i.identity[0].clone()));
-      for j in 1..i.identity.len() {
-        out.push_str(" = ");
-        out.push_str(&pretty(*i.identity[j].clone()));
-      }
+    AST::Call(c) => format!(
+      "{}({})",
+      c.name,
+      c.call
+        .iter()
+        .map(|arg| child(arg, EXPR))
+        .collect::<Vec<String>>()
+        .join(", ")
+    ),
+    AST::Compare(c) => format!(
+      "{} {} {}",
+      child(&c.compare.0, EXPR),
+      relation(&c.relation),
+      child(&c.compare.1, EXPR)
+    ),
+    AST::Piecewise(p) => {
+      let mut cases: Vec<String> = p
+        .piecewise
+        .iter()
+        .map(|(condition, value)| format!("{}: {}", child(condition, COMPARE), child(value, EXPR)))
+        .collect();
+      cases.extend(p.otherwise.iter().map(|o| child(o, EXPR)));
+      format!("{{{}}}", cases.join("; "))
+    }
+    AST::Points(p) => format!(
+      "points: {}",
+      p.points
+        .iter()
+        .map(|(x, y)| format!("({}, {})", child(x, EXPR), child(y, EXPR)))
+        .collect::<Vec<String>>()
+        .join(", ")
+    ),
+    AST::Statement(s) => pretty(*s.statement),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::syntax::parser::{
+    Call, Compare, Expr, Index, Parser, Piecewise, Relation, Term, Unary, FUNCTIONS,
+  };
+  use proptest::prelude::*;
+
+  fn parse(input: &str) -> AST {
+    *Parser::new(input.to_string()).parse_expression().unwrap()
+  }
+
+  // brackets are only kept where the parser needs them
+  #[test]
+  fn minimal() {
+    for input in [
+      "2x + 1",
+      "3 * (x + 1)^2 - (x - 2)",
+      "a + b - c",
+      "a - (b - c)",
+      "a / b * c",
+      "a / (b * c)",
+      "-x^2",
+      "(-x)^2",
+      "a^b^c",
+      "(a^b)^c",
+      "+(-x)",
+      "(-b ± sqrt(b^2 - 4a * c)) / (2a)",
+      "|x - 1| + 2 * |x|",
+      "(x + 1)! mod 3",
+      "||a| * |b||",
+      "{x < 0: -x; x >= 1: floor(x); x}",
+      "0 * x + 0 * xb + 0 * Xa^2 + 0ab",
+    ] {
+      assert_eq!(pretty(parse(input)), input);
+    }
+  }
+
+  fn sign(signs: &'static [Sign]) -> impl Strategy<Value = Sign> {
+    proptest::sample::select(signs)
+  }
+
+  // trees in the shape the parser builds them
+  fn tree() -> impl Strategy<Value = AST> {
+    let leaf = prop_oneof![
+      (0u32..100000).prop_map(|n| AST::Number(n as f64 / 100f64)),
+      // `mod` is read as the operator
+      "[a-z]{1,3}"
+        .prop_filter("mod is an operator", |v| v != "mod")
+        .prop_map(AST::Variable),
+      // zero times a name starting with x, which must not print as a hex literal such as `0xb`
+      "[xX][a-f]{0,2}".prop_map(|name| AST::Term(Term {
+        sign: Sign::Mul,
+        term: vec![Box::new(AST::Number(0f64)), Box::new(AST::Variable(name))],
+      })),
+    ];
+    leaf.prop_recursive(6, 48, 4, |inner| {
+      prop_oneof![
+        (
+          sign(&[Sign::Add, Sign::Sub, Sign::AddSub]),
+          prop::collection::vec(inner.clone(), 2..4)
+        )
+          .prop_map(|(sign, expr)| {
+            flatten(AST::Expr(Expr {
+              sign,
+              expr: expr.into_iter().map(Box::new).collect(),
+            }))
+          }),
+        (
+          sign(&[Sign::Mul, Sign::Div, Sign::Mod]),
+          prop::collection::vec(inner.clone(), 2..4)
+        )
+          .prop_map(|(sign, term)| {
+            flatten(AST::Term(Term {
+              sign,
+              term: term.into_iter().map(Box::new).collect(),
+            }))
+          }),
+        (inner.clone(), inner.clone()).prop_map(|(a, b)| AST::Index(Index {
+          sign: Sign::Pow,
+          index: (Box::new(a), Box::new(b)),
+        })),
+        (sign(&[Sign::Add, Sign::Sub, Sign::AddSub]), inner.clone()).prop_map(|(sign, a)| {
+          AST::Unary(Unary {
+            sign,
+            unary: Box::new(a),
+          })
+        }),
+        (
+          proptest::sample::select(&FUNCTIONS[..]),
+          prop::collection::vec(inner.clone(), 1..3)
+        )
+          .prop_map(|(name, call)| AST::Call(Call {
+            name: name.to_string(),
+            call: call.into_iter().map(Box::new).collect(),
+          })),
+        (
+          prop::collection::vec(
+            (
+              proptest::sample::select(&[
+                Relation::Less,
+                Relation::Greater,
+                Relation::LessEqual,
+                Relation::GreaterEqual,
+              ]),
+              inner.clone(),
+              inner.clone(),
+              inner.clone()
+            ),
+            1..3
+          ),
+          proptest::option::of(inner)
+        )
+          .prop_map(|(cases, otherwise)| AST::Piecewise(Piecewise {
+            piecewise: cases
+              .into_iter()
+              .map(|(relation, a, b, value)| {
+                (
+                  Box::new(AST::Compare(Compare {
+                    relation,
+                    compare: (Box::new(a), Box::new(b)),
+                  })),
+                  Box::new(value),
+                )
+              })
+              .collect(),
+            otherwise: otherwise.map(Box::new),
+          })),
+      ]
+    })
+  }
+
+  fn chain(ast: &AST, sign: &Sign) -> bool {
+    match ast {
+      AST::Expr(e) => e.sign == *sign,
+      AST::Term(t) => t.sign == *sign,
+      _ => false,
     }
-    AST::Call(c) => {
-      out.push_str(&c.name);
-      out.push_str("(");
-      out.push_str(&pretty(*c.call[0].clone()));
-      for j in 1..c.call.len() {
-        out.push_str(", ");
-        out.push_str(&pretty(*c.call[j].clone()));
+  }
+
+  // merge a chain on the left into its parent, as the parser does with `a + b + c`
+  fn flatten(ast: AST) -> AST {
+    match ast {
+      AST::Expr(mut e) if chain(&e.expr[0], &e.sign) => {
+        if let AST::Expr(first) = *e.expr.remove(0) {
+          e.expr.splice(0..0, first.expr);
+        }
+        AST::Expr(e)
+      }
+      AST::Term(mut t) if chain(&t.term[0], &t.sign) => {
+        if let AST::Term(first) = *t.term.remove(0) {
+          t.term.splice(0..0, first.term);
+        }
+        AST::Term(t)
       }
-      out.push_str(")");
+      ast => ast,
     }
-    AST::Statement(s) => {
-      out.push_str(&pretty(*s.statement.clone()));
+  }
+
+  proptest! {
+    // printing a tree and parsing it again gives the same tree
+    #[test]
+    fn round_trip(ast in tree()) {
+      let printed = pretty(ast.clone());
+      let parsed = Parser::new(printed.clone()).parse_expression();
+      prop_assert_eq!(parsed.map(|p| *p), Ok(ast), "{}", printed);
     }
   }
-  out
 }
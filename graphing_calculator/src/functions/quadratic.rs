@@ -1,6 +1,195 @@
-use super::expand;
+use super::{expand, variables};
 use crate::syntax::parser::{Call, Expr, Identity, Index, Sign, Term, Unary, AST};
 
+// the highest whole power of a bracket that is multiplied out, so `(x + 1)^2` becomes
+// `x^2 + 2x + 1` without `(x + 1)^1000` running away
+const MAX_POWER: f64 = 8f64;
+
+// `n * factors / over * var^power`, where neither factors nor over hold var unless they could
+// not be taken apart
+#[derive(Debug, Clone, PartialEq)]
+struct Monomial {
+  n: f64,
+  factors: Vec<AST>,
+  over: Vec<AST>,
+  power: u32,
+}
+
+impl Monomial {
+  fn like(&self, other: &Monomial) -> bool {
+    self.power == other.power && self.factors == other.factors && self.over == other.over
+  }
+
+  // the coefficient of var^power, or the whole monomial once var has been put in its factors
+  fn ast(self) -> AST {
+    let mut factors: Vec<Box<AST>> = self.factors.into_iter().map(Box::new).collect();
+    let product = match factors.len() {
+      0 => None,
+      1 => Some(*factors.remove(0)),
+      _ => Some(AST::Term(Term {
+        sign: Sign::Mul,
+        term: factors,
+      })),
+    };
+    let product = match (self.n, product) {
+      (n, None) => AST::Number(n),
+      (1f64, Some(p)) => p,
+      (-1f64, Some(p)) => AST::Unary(Unary {
+        sign: Sign::Sub,
+        unary: Box::new(p),
+      }),
+      (n, Some(AST::Term(mut t))) if t.sign == Sign::Mul => {
+        t.term.insert(0, Box::new(AST::Number(n)));
+        AST::Term(t)
+      }
+      (n, Some(p)) => AST::Term(Term {
+        sign: Sign::Mul,
+        term: vec![Box::new(AST::Number(n)), Box::new(p)],
+      }),
+    };
+    if self.over.is_empty() {
+      return product;
+    }
+    let mut term = vec![Box::new(product)];
+    term.extend(self.over.into_iter().map(Box::new));
+    AST::Term(Term {
+      sign: Sign::Div,
+      term,
+    })
+  }
+}
+
+// a sum of monomials in one variable, with like terms collected
+#[derive(Debug, Clone, PartialEq)]
+struct Polynomial {
+  terms: Vec<Monomial>,
+}
+
+impl Polynomial {
+  fn constant(n: f64) -> Polynomial {
+    Polynomial { terms: vec![] }.add(
+      Polynomial {
+        terms: vec![Monomial {
+          n,
+          factors: vec![],
+          over: vec![],
+          power: 0,
+        }],
+      },
+      1f64,
+    )
+  }
+
+  // a part that does not break down further, such as another variable or a call
+  fn factor(ast: AST) -> Polynomial {
+    Polynomial {
+      terms: vec![Monomial {
+        n: 1f64,
+        factors: vec![ast],
+        over: vec![],
+        power: 0,
+      }],
+    }
+  }
+
+  fn add(mut self, other: Polynomial, sign: f64) -> Polynomial {
+    for mut monomial in other.terms {
+      monomial.n *= sign;
+      match self.terms.iter().position(|m| m.like(&monomial)) {
+        Some(i) => {
+          self.terms[i].n += monomial.n;
+          if self.terms[i].n == 0f64 {
+            self.terms.remove(i);
+          }
+        }
+        None if monomial.n != 0f64 => self.terms.push(monomial),
+        None => {}
+      }
+    }
+    self
+  }
+
+  fn mul(self, other: &Polynomial) -> Polynomial {
+    let mut out = Polynomial { terms: vec![] };
+    for a in &self.terms {
+      for b in &other.terms {
+        let product = Monomial {
+          n: a.n * b.n,
+          factors: a.factors.iter().chain(b.factors.iter()).cloned().collect(),
+          over: a.over.iter().chain(b.over.iter()).cloned().collect(),
+          power: a.power + b.power,
+        };
+        out = out.add(
+          Polynomial {
+            terms: vec![product],
+          },
+          1f64,
+        );
+      }
+    }
+    out
+  }
+
+  // multiply out sums, products, quotients and whole powers into monomials in var
+  fn from(ast: &AST, var: &str) -> Polynomial {
+    let holds = |ast: &AST| variables(ast).contains(var);
+    match ast {
+      AST::Number(n) => Polynomial::constant(*n),
+      AST::Variable(v) if v == var => Polynomial {
+        terms: vec![Monomial {
+          n: 1f64,
+          factors: vec![],
+          over: vec![],
+          power: 1,
+        }],
+      },
+      AST::Statement(s) => Polynomial::from(&s.statement, var),
+      AST::Unary(u) if u.sign == Sign::Add => Polynomial::from(&u.unary, var),
+      AST::Unary(u) if u.sign == Sign::Sub => {
+        Polynomial::constant(0f64).add(Polynomial::from(&u.unary, var), -1f64)
+      }
+      AST::Expr(e) if e.sign == Sign::Add || e.sign == Sign::Sub => {
+        let mut out = Polynomial::constant(0f64);
+        for (i, child) in e.expr.iter().enumerate() {
+          let sign = if i > 0 && e.sign == Sign::Sub {
+            -1f64
+          } else {
+            1f64
+          };
+          out = out.add(Polynomial::from(child, var), sign);
+        }
+        out
+      }
+      AST::Term(t) if t.sign == Sign::Mul => t
+        .term
+        .iter()
+        .fold(Polynomial::constant(1f64), |out, child| {
+          out.mul(&Polynomial::from(child, var))
+        }),
+      // only dividing by something without var keeps a polynomial
+      AST::Term(t) if t.sign == Sign::Div && !t.term[1..].iter().any(|d| holds(d)) => {
+        let mut out = Polynomial::from(&t.term[0], var);
+        for divisor in &t.term[1..] {
+          match expand(*divisor.clone()) {
+            AST::Number(n) if n != 0f64 => out.terms.iter_mut().for_each(|m| m.n /= n),
+            d => out.terms.iter_mut().for_each(|m| m.over.push(d.clone())),
+          }
+        }
+        out
+      }
+      AST::Index(i) if i.sign == Sign::Pow && holds(&i.index.0) => match expand(*i.index.1.clone())
+      {
+        AST::Number(n) if n.fract() == 0f64 && (0f64..=MAX_POWER).contains(&n) => {
+          let base = Polynomial::from(&i.index.0, var);
+          (0..n as u32).fold(Polynomial::constant(1f64), |out, _| out.mul(&base))
+        }
+        _ => Polynomial::factor(ast.clone()),
+      },
+      _ => Polynomial::factor(ast.clone()),
+    }
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct Quadratic {
   pub a: AST,
@@ -14,235 +203,99 @@ impl Quadratic {
     Quadratic { a, b, c, var }
   }
 
-  pub fn from(ast: AST, var: String) -> Quadratic {
-    match ast {
-      AST::Expr(expr) => {
-        let mut quadratic = Quadratic::new(
-          AST::Number(0f64),
-          AST::Number(0f64),
-          AST::Number(0f64),
-          var.clone(),
-        );
-        for child in expr.expr {
-          match *child {
-            AST::Term(term) => {
-              if term.term.contains(&Box::new(AST::Index(Index {
-                sign: Sign::Pow,
-                index: (
-                  Box::new(AST::Variable(var.clone())),
-                  Box::new(AST::Number(2f64)),
-                ),
-              }))) {
-                let mut t = term.clone();
-                t.term.remove(
-                  t.term
-                    .iter()
-                    .position(|x| {
-                      *x == Box::new(AST::Index(Index {
-                        sign: Sign::Pow,
-                        index: (
-                          Box::new(AST::Variable(var.clone())),
-                          Box::new(AST::Number(2f64)),
-                        ),
-                      }))
-                    })
-                    .unwrap(),
-                );
-                if t.term.len() == 0 {
-                  if quadratic.a == AST::Number(0f64) {
-                    quadratic.a = AST::Number(1f64);
-                  } else {
-                    quadratic.a = AST::Expr(Expr {
-                      sign: Sign::Add,
-                      expr: vec![Box::new(quadratic.a), Box::new(AST::Number(1f64))],
-                    });
-                  }
-                } else {
-                  if quadratic.a == AST::Number(0f64) {
-                    quadratic.a = AST::Term(t);
-                  } else {
-                    quadratic.a = AST::Expr(Expr {
-                      sign: Sign::Add,
-                      expr: vec![Box::new(quadratic.a), Box::new(AST::Term(t))],
-                    });
-                  }
-                }
-              } else if term.term.contains(&Box::new(AST::Variable(var.clone()))) {
-                let mut t = term.clone();
-                t.term.remove(
-                  t.term
-                    .iter()
-                    .position(|x| *x == Box::new(AST::Variable(var.clone())))
-                    .unwrap(),
-                );
-                if t.term.len() == 0 {
-                  if quadratic.b == AST::Number(0f64) {
-                    quadratic.b = AST::Number(1f64);
-                  } else {
-                    quadratic.b = AST::Expr(Expr {
-                      sign: Sign::Add,
-                      expr: vec![Box::new(quadratic.b), Box::new(AST::Number(1f64))],
-                    });
-                  }
-                } else {
-                  if quadratic.b == AST::Number(0f64) {
-                    quadratic.b = AST::Term(t);
-                  } else {
-                    quadratic.b = AST::Expr(Expr {
-                      sign: Sign::Add,
-                      expr: vec![Box::new(quadratic.b), Box::new(AST::Term(t))],
-                    });
-                  }
-                }
-              } else {
-                if quadratic.c == AST::Number(0f64) {
-                  quadratic.c = AST::Term(term);
-                } else {
-                  quadratic.c = AST::Expr(Expr {
-                    sign: Sign::Add,
-                    expr: vec![Box::new(quadratic.c), Box::new(AST::Term(term))],
-                  });
-                }
-              }
-            }
-            AST::Variable(v) => {
-              if v != var.clone() {
-                if quadratic.c == AST::Number(0f64) {
-                  quadratic.c = AST::Variable(v).clone();
-                } else {
-                  quadratic.c = AST::Expr(Expr {
-                    sign: Sign::Add,
-                    expr: vec![Box::new(quadratic.c), Box::new(AST::Variable(v))],
-                  });
-                }
-              } else {
-                if quadratic.b == AST::Number(0f64) {
-                  quadratic.b = AST::Number(1f64);
-                } else {
-                  quadratic.b = AST::Expr(Expr {
-                    sign: Sign::Add,
-                    expr: vec![Box::new(quadratic.b), Box::new(AST::Number(1f64))],
-                  });
-                }
-              }
-            }
-            AST::Number(n) => {
-              if quadratic.c == AST::Number(0f64) {
-                quadratic.c = AST::Number(n).clone();
-              } else {
-                quadratic.c = AST::Expr(Expr {
-                  sign: Sign::Add,
-                  expr: vec![Box::new(quadratic.c), Box::new(AST::Number(n))],
-                });
-              }
-            }
-            other => {
-              if quadratic.c == AST::Number(0f64) {
-                quadratic.c = other.clone();
-              } else {
-                quadratic.c = AST::Expr(Expr {
-                  sign: Sign::Add,
-                  expr: vec![Box::new(quadratic.c), Box::new(other)],
-                });
-              }
-            }
-          }
-        }
-        quadratic
+  // sort the terms of a statement, written as anything equal to zero, by their power of var,
+  // failing on a term that is not a power of var up to 2, such as `sin(x)` or `x^3`
+  pub fn from(ast: AST, var: String) -> Result<Quadratic, String> {
+    let polynomial = match ast {
+      AST::Statement(statement) => return Quadratic::from(*statement.statement, var),
+      AST::Identity(identity) if identity.identity.len() == 2 => {
+        Polynomial::from(&identity.identity[0], &var)
+          .add(Polynomial::from(&identity.identity[1], &var), -1f64)
       }
-      AST::Statement(statement) => Quadratic::from(*statement.statement, var),
-      AST::Identity(identity) => Quadratic::one_side(
-        Quadratic::from(
-          match *identity.identity[0].clone() {
-            AST::Expr(expr) => AST::Expr(expr),
-            _ => AST::Expr(Expr {
-              sign: Sign::Add,
-              expr: vec![identity.identity[0].clone()],
-            }),
-          },
-          var.clone(),
-        ),
-        Quadratic::from(
-          match *identity.identity[1].clone() {
-            AST::Expr(expr) => AST::Expr(expr),
-            _ => AST::Expr(Expr {
-              sign: Sign::Add,
-              expr: vec![identity.identity[1].clone()],
-            }),
-          },
-          var.clone(),
-        ),
-        var.clone(),
-      ),
-      _ => panic!("Unsupported type, got: {:?}", ast),
+      other => Polynomial::from(&other, &var),
+    };
+    let mut powers: [Vec<Box<AST>>; 3] = [vec![], vec![], vec![]];
+    for monomial in polynomial.terms {
+      let opaque = monomial
+        .factors
+        .iter()
+        .chain(monomial.over.iter())
+        .any(|f| variables(f).contains(&var));
+      if monomial.power > 2 || opaque {
+        return Err(format!("Not quadratic in {}.", var));
+      }
+      powers[monomial.power as usize].push(Box::new(monomial.ast()));
     }
+    let [c, b, a] = powers.map(|terms| match terms.len() {
+      0 => AST::Number(0f64),
+      1 => *terms[0].clone(),
+      _ => AST::Expr(Expr {
+        sign: Sign::Add,
+        expr: terms,
+      }),
+    });
+    Ok(Quadratic::new(a, b, c, var))
   }
 
-  pub fn one_side(q1: Quadratic, q2: Quadratic, var: String) -> Quadratic {
-    return Quadratic::new(
-      AST::Expr(Expr {
-        sign: Sign::Sub,
-        expr: vec![Box::new(q1.a), Box::new(q2.a)],
-      }),
-      AST::Expr(Expr {
-        sign: Sign::Sub,
-        expr: vec![Box::new(q1.b), Box::new(q2.b)],
-      }),
-      AST::Expr(Expr {
-        sign: Sign::Sub,
-        expr: vec![Box::new(q1.c), Box::new(q2.c)],
-      }),
-      var,
-    );
+  // `b^2 - 4ac`, which decides how many real roots there are
+  pub fn discriminant(&self) -> AST {
+    AST::Expr(Expr {
+      sign: Sign::Sub,
+      expr: vec![
+        Box::new(AST::Index(Index {
+          sign: Sign::Pow,
+          index: (Box::new(self.b.clone()), Box::new(AST::Number(2f64))),
+        })),
+        Box::new(AST::Term(Term {
+          sign: Sign::Mul,
+          term: vec![
+            Box::new(AST::Number(4f64)),
+            Box::new(self.a.clone()),
+            Box::new(self.c.clone()),
+          ],
+        })),
+      ],
+    })
+  }
+
+  // the quadratic formula with the discriminant given, so it can be shown before or after simplifying
+  pub fn formula(&self, discriminant: AST) -> AST {
+    AST::Identity(Identity {
+      identity: vec![
+        Box::new(AST::Variable(self.var.clone())),
+        Box::new(AST::Term(Term {
+          sign: Sign::Div,
+          term: vec![
+            Box::new(AST::Expr(Expr {
+              sign: Sign::AddSub,
+              expr: vec![
+                Box::new(AST::Unary(Unary {
+                  sign: Sign::Sub,
+                  unary: Box::new(self.b.clone()),
+                })),
+                Box::new(AST::Call(Call {
+                  name: "sqrt".to_string(),
+                  call: vec![Box::new(discriminant)],
+                })),
+              ],
+            })),
+            Box::new(AST::Term(Term {
+              sign: Sign::Mul,
+              term: vec![Box::new(AST::Number(2f64)), Box::new(self.a.clone())],
+            })),
+          ],
+        })),
+      ],
+    })
   }
 
-  pub fn solve(&self) -> AST {
+  // `var = ...` by the quadratic formula, or from `bx + c = 0` when there is no squared term,
+  // failing when var cancels out and the equation does not depend on it
+  pub fn solve(&self) -> Result<AST, String> {
     if !matches!(expand(self.a.clone()), AST::Number(x) if x == 0f64) {
-      AST::Identity(Identity {
-        identity: vec![
-          Box::new(AST::Variable(self.var.clone())),
-          Box::new(AST::Term(Term {
-            sign: Sign::Div,
-            term: vec![
-              Box::new(AST::Expr(Expr {
-                sign: Sign::AddSub,
-                expr: vec![
-                  Box::new(AST::Unary(Unary {
-                    sign: Sign::Sub,
-                    unary: Box::new(self.b.clone()),
-                  })),
-                  Box::new(AST::Call(Call {
-                    name: "sqrt".to_string(),
-                    call: vec![Box::new(AST::Expr(Expr {
-                      sign: Sign::Sub,
-                      expr: vec![
-                        Box::new(AST::Index(Index {
-                          sign: Sign::Pow,
-                          index: (Box::new(self.b.clone()), Box::new(AST::Number(2f64))),
-                        })),
-                        Box::new(AST::Term(Term {
-                          sign: Sign::Mul,
-                          term: vec![
-                            Box::new(AST::Number(4f64)),
-                            Box::new(self.a.clone()),
-                            Box::new(self.c.clone()),
-                          ],
-                        })),
-                      ],
-                    }))],
-                  })),
-                ],
-              })),
-              Box::new(AST::Term(Term {
-                sign: Sign::Mul,
-                term: vec![Box::new(AST::Number(2f64)), Box::new(self.a.clone())],
-              })),
-            ],
-          })),
-        ],
-      })
+      Ok(self.formula(self.discriminant()))
     } else if !matches!(expand(self.b.clone()), AST::Number(x) if x == 0f64) {
-      AST::Identity(Identity {
+      Ok(AST::Identity(Identity {
         identity: vec![
           Box::new(AST::Variable(self.var.clone())),
           Box::new(AST::Term(Term {
@@ -256,36 +309,92 @@ impl Quadratic {
             ],
           })),
         ],
-      })
-    } else if !matches!(expand(self.c.clone()), AST::Number(x) if x == 0f64) {
-      AST::Identity(Identity {
-        identity: vec![
-          Box::new(AST::Variable(self.var.clone())),
-          Box::new(self.c.clone()),
-        ],
-      })
-    } else if self.var == "y".to_string() {
-      Quadratic::from(
-        AST::Identity(Identity {
-          identity: vec![
-            Box::new(AST::Variable(self.var.clone())),
-            Box::new(self.c.clone()),
-          ],
-        }),
-        "x".to_string(),
-      )
-      .solve()
+      }))
     } else {
+      Err(format!("{} cancels out of the equation.", self.var))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::functions::pretty;
+  use crate::syntax::parser::Parser;
+
+  fn coefficients(input: &str, var: &str) -> [String; 3] {
+    let ast = *Parser::new(input.to_string() + ";").parse().unwrap()[0].clone();
+    let quad = Quadratic::from(ast, var.to_string()).unwrap();
+    [quad.a, quad.b, quad.c].map(|side| pretty(expand(side)))
+  }
+
+  // squares written as products or brackets are still the x^2 term
+  #[test]
+  fn squares() {
+    assert_eq!(coefficients("x * x = 4", "x"), ["1", "0", "-4"]);
+    assert_eq!(coefficients("(2x)^2 + x = 0", "x"), ["4", "1", "0"]);
+    assert_eq!(coefficients("x^2 = 4", "x"), ["1", "0", "-4"]);
+    assert_eq!(coefficients("(x + 1)^2 = 0", "x"), ["1", "2", "1"]);
+  }
+
+  // dividing by a number scales the coefficient, dividing by another variable keeps it
+  #[test]
+  fn quotients() {
+    assert_eq!(
+      coefficients("x^2 / 4 + x / 2 = 1", "x"),
+      ["0.25", "0.5", "-1"]
+    );
+    assert_eq!(coefficients("x^2 / a = 1", "x"), ["1 / a", "0", "-1"]);
+  }
+
+  // negated terms and subtracted terms keep their sign
+  #[test]
+  fn signs() {
+    assert_eq!(coefficients("y = x - 12", "x"), ["0", "-1", "12 + y"]);
+    assert_eq!(coefficients("-x^2 - (3 - x) = 0", "x"), ["-1", "1", "-3"]);
+    assert_eq!(coefficients("2x^2 - 3x * y = 0", "x"), ["2", "y * -3", "0"]);
+  }
+
+  // like terms from either side are collected into one coefficient
+  #[test]
+  fn like_terms() {
+    assert_eq!(
+      coefficients("x^2 + 2x = x * x + x + y", "x"),
+      ["0", "1", "-y"]
+    );
+  }
+
+  // terms that are not a power of x up to 2 are refused, while other variables may be anything
+  #[test]
+  fn opaque() {
+    let quadratic = |input: &str| {
       Quadratic::from(
-        AST::Identity(Identity {
-          identity: vec![
-            Box::new(AST::Variable(self.var.clone())),
-            Box::new(self.c.clone()),
-          ],
-        }),
-        "y".to_string(),
+        *Parser::new(input.to_string() + ";").parse().unwrap()[0].clone(),
+        "x".to_string(),
       )
-      .solve()
+    };
+    for input in ["sin(x) + x = 0", "x^3 + x^2 = 0", "x^3 = 8", "1 / x = 2"] {
+      assert_eq!(
+        quadratic(input).unwrap_err(),
+        "Not quadratic in x.",
+        "{}",
+        input
+      );
+    }
+    assert_eq!(coefficients("sin(y) + x = 0", "x"), ["0", "1", "sin(y)"]);
+  }
+
+  // an equation that does not depend on the variable has no solution for it, rather than one
+  // for some other variable
+  #[test]
+  fn cancels() {
+    for input in ["x = 3", "y - y = x"] {
+      let ast = *Parser::new(input.to_string() + ";").parse().unwrap()[0].clone();
+      let quadratic = Quadratic::from(expand(ast), "y".to_string()).unwrap();
+      assert_eq!(
+        quadratic.solve(),
+        Err("y cancels out of the equation.".to_string())
+      );
     }
   }
 }
@@ -0,0 +1,22 @@
+// coefficients are rounded to this many decimal places when written out, so the error of a
+// numerical method does not show in the equations
+const PLACES: i32 = 6;
+
+pub fn round(n: f64) -> f64 {
+  let scale = 10f64.powi(PLACES);
+  // adding zero turns a negative zero into a positive one
+  (n * scale).round() / scale + 0f64
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // the error of a difference is rounded away, without leaving a negative zero behind
+  #[test]
+  fn places() {
+    assert_eq!(round(1.9999999999), 2f64);
+    assert_eq!(round(0.1234564), 0.123456);
+    assert!(round(-0.0000001).is_sign_positive());
+  }
+}
@@ -0,0 +1,195 @@
+use super::{expand, quadratic::Quadratic};
+use crate::syntax::parser::{Expr, Identity, Index, Sign, Term, Unary, AST};
+
+// one rewrite in a worked solution, named after the rule that was applied
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+  pub rule: &'static str,
+  pub ast: AST,
+}
+
+fn zero(ast: &AST) -> bool {
+  matches!(ast, AST::Number(n) if *n == 0f64)
+}
+
+fn equals_zero(side: AST) -> AST {
+  AST::Identity(Identity {
+    identity: vec![Box::new(side), Box::new(AST::Number(0f64))],
+  })
+}
+
+// `a x^2 + b x + c`, leaving out the terms with no coefficient
+fn collected(quad: &Quadratic, var: &str) -> AST {
+  let powers = [
+    (
+      &quad.a,
+      AST::Index(Index {
+        sign: Sign::Pow,
+        index: (
+          Box::new(AST::Variable(var.to_string())),
+          Box::new(AST::Number(2f64)),
+        ),
+      }),
+    ),
+    (&quad.b, AST::Variable(var.to_string())),
+  ];
+  let mut terms: Vec<Box<AST>> = Vec::new();
+  for (coefficient, power) in powers {
+    match coefficient {
+      c if zero(c) => {}
+      AST::Number(n) if *n == 1f64 => terms.push(Box::new(power)),
+      c => terms.push(Box::new(AST::Term(Term {
+        sign: Sign::Mul,
+        term: vec![Box::new(c.clone()), Box::new(power)],
+      }))),
+    }
+  }
+  match &quad.c {
+    AST::Expr(e) if e.sign == Sign::Add => terms.extend(e.expr.clone()),
+    c if !zero(c) || terms.is_empty() => terms.push(Box::new(c.clone())),
+    _ => {}
+  }
+  // a negative constant is taken away, so the equation reads `3x - 6 = 0` rather than `3x + -6`
+  let constant = match terms.last().map(|t| t.as_ref()) {
+    Some(AST::Number(n)) if terms.len() > 1 && n.is_sign_negative() => Some(-n),
+    _ => None,
+  };
+  if constant.is_some() {
+    terms.pop();
+  }
+  let sum = if terms.len() == 1 {
+    *terms.remove(0)
+  } else {
+    AST::Expr(Expr {
+      sign: Sign::Add,
+      expr: terms,
+    })
+  };
+  match constant {
+    Some(n) => AST::Expr(Expr {
+      sign: Sign::Sub,
+      expr: vec![Box::new(sum), Box::new(AST::Number(n))],
+    }),
+    None => sum,
+  }
+}
+
+// every step taken to solve an equation for var, ending with the same answer `solve` gives
+pub fn steps(ast: AST, var: &str) -> Result<Vec<Step>, String> {
+  let mut out: Vec<Step> = Vec::new();
+  let mut push = |rule: &'static str, ast: AST| {
+    if out.last().is_none_or(|last| last.ast != ast) {
+      out.push(Step { rule, ast });
+    }
+  };
+
+  let expanded = expand(ast);
+  push("expanded", expanded.clone());
+  if let AST::Identity(identity) = &expanded {
+    if identity.identity.len() == 2 && !zero(&identity.identity[1]) {
+      push(
+        "moved terms to one side",
+        equals_zero(AST::Expr(Expr {
+          sign: Sign::Sub,
+          expr: identity.identity.clone(),
+        })),
+      );
+    }
+  }
+
+  let quad = Quadratic::from(expanded, var.to_string())?;
+  let quad = Quadratic::new(
+    expand(quad.a),
+    expand(quad.b),
+    expand(quad.c),
+    var.to_string(),
+  );
+  push("collected like terms", equals_zero(collected(&quad, var)));
+
+  if !zero(&quad.a) {
+    push(
+      "substituted into the quadratic formula",
+      quad.formula(quad.discriminant()),
+    );
+    push(
+      "simplified the discriminant",
+      quad.formula(expand(quad.discriminant())),
+    );
+  } else if !zero(&quad.b) {
+    push(
+      "divided by the coefficient",
+      AST::Identity(Identity {
+        identity: vec![
+          Box::new(AST::Variable(var.to_string())),
+          Box::new(AST::Term(Term {
+            sign: Sign::Div,
+            term: vec![
+              Box::new(AST::Unary(Unary {
+                sign: Sign::Sub,
+                unary: Box::new(quad.c.clone()),
+              })),
+              Box::new(quad.b.clone()),
+            ],
+          })),
+        ],
+      }),
+    );
+  }
+  push("simplified", expand(quad.solve()?));
+  Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::functions::pretty;
+  use crate::syntax::parser::Parser;
+
+  fn worked(input: &str, var: &str) -> Vec<(&'static str, String)> {
+    let ast = *Parser::new(input.to_string() + ";").parse().unwrap()[0].clone();
+    steps(ast, var)
+      .unwrap()
+      .into_iter()
+      .map(|s| (s.rule, pretty(s.ast)))
+      .collect()
+  }
+
+  // a quadratic goes through the formula and its discriminant is simplified on its own
+  #[test]
+  fn quadratic() {
+    assert_eq!(
+      worked("2y^2 + 10y + x + 1 = 0", "y"),
+      vec![
+        ("expanded", "1 + y^2 * 2 + y * 10 + x = 0".to_string()),
+        ("collected like terms", "2y^2 + 10y + 1 + x = 0".to_string()),
+        (
+          "substituted into the quadratic formula",
+          "y = (-10 ± sqrt(10^2 - 4 * 2 * (1 + x))) / (2 * 2)".to_string()
+        ),
+        (
+          "simplified the discriminant",
+          "y = (-10 ± sqrt(100 - (1 + x) * 8)) / (2 * 2)".to_string()
+        ),
+        (
+          "simplified",
+          "y = (-10 ± sqrt(100 - (1 + x) * 8)) / 4".to_string()
+        ),
+      ]
+    );
+  }
+
+  // a linear equation is moved to one side and divided through
+  #[test]
+  fn linear() {
+    assert_eq!(
+      worked("3x = 6", "x"),
+      vec![
+        ("expanded", "x * 3 = 6".to_string()),
+        ("moved terms to one side", "x * 3 - 6 = 0".to_string()),
+        ("collected like terms", "3x - 6 = 0".to_string()),
+        ("divided by the coefficient", "x = -(-6) / 3".to_string()),
+        ("simplified", "x = 2".to_string()),
+      ]
+    );
+  }
+}
@@ -0,0 +1,122 @@
+use super::eval::constant;
+use crate::syntax::parser::{
+  Call, Compare, Expr, Identity, Index, Piecewise, Points, Statement, Term, Unary, AST,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+// replace every occurrence of a variable with another expression
+pub fn substitute(ast: AST, var: &str, replacement: &AST) -> AST {
+  substitute_all(
+    ast,
+    &BTreeMap::from([(var.to_string(), replacement.clone())]),
+  )
+}
+
+// replace several variables at once. a replacement is never substituted into itself, so
+// `x -> y, y -> x` swaps the two rather than leaving both as x
+pub fn substitute_all(ast: AST, replacements: &BTreeMap<String, AST>) -> AST {
+  let mut sub = |a: Box<AST>| Box::new(substitute_all(*a, replacements));
+  match ast {
+    AST::Variable(v) if replacements.contains_key(&v) => replacements[&v].clone(),
+    AST::Expr(e) => AST::Expr(Expr {
+      sign: e.sign,
+      expr: e.expr.into_iter().map(sub).collect(),
+    }),
+    AST::Term(t) => AST::Term(Term {
+      sign: t.sign,
+      term: t.term.into_iter().map(sub).collect(),
+    }),
+    AST::Index(i) => AST::Index(Index {
+      sign: i.sign,
+      index: (sub(i.index.0), sub(i.index.1)),
+    }),
+    AST::Unary(u) => AST::Unary(Unary {
+      sign: u.sign,
+      unary: sub(u.unary),
+    }),
+    AST::Call(c) => AST::Call(Call {
+      name: c.name,
+      call: c.call.into_iter().map(sub).collect(),
+    }),
+    AST::Identity(i) => AST::Identity(Identity {
+      identity: i.identity.into_iter().map(sub).collect(),
+    }),
+    AST::Compare(c) => AST::Compare(Compare {
+      relation: c.relation,
+      compare: (sub(c.compare.0), sub(c.compare.1)),
+    }),
+    AST::Piecewise(p) => AST::Piecewise(Piecewise {
+      piecewise: p
+        .piecewise
+        .into_iter()
+        .map(|(condition, value)| (sub(condition), sub(value)))
+        .collect(),
+      otherwise: p.otherwise.map(sub),
+    }),
+    AST::Points(p) => AST::Points(Points {
+      points: p
+        .points
+        .into_iter()
+        .map(|(x, y)| (sub(x), sub(y)))
+        .collect(),
+    }),
+    AST::Statement(s) => AST::Statement(Statement {
+      statement: sub(s.statement),
+      commands: s.commands.into_iter().map(|c| c.map(&mut sub)).collect(),
+    }),
+    other => other,
+  }
+}
+
+// every variable name used in an expression, leaving out constants such as π
+pub fn variables(ast: &AST) -> BTreeSet<String> {
+  let children: Vec<&AST> = match ast {
+    AST::Variable(v) if constant(v).is_some() => vec![],
+    AST::Variable(v) => return BTreeSet::from([v.clone()]),
+    AST::Expr(e) => e.expr.iter().map(|a| a.as_ref()).collect(),
+    AST::Term(t) => t.term.iter().map(|a| a.as_ref()).collect(),
+    AST::Index(i) => vec![i.index.0.as_ref(), i.index.1.as_ref()],
+    AST::Unary(u) => vec![u.unary.as_ref()],
+    AST::Call(c) => c.call.iter().map(|a| a.as_ref()).collect(),
+    AST::Identity(i) => i.identity.iter().map(|a| a.as_ref()).collect(),
+    AST::Compare(c) => vec![c.compare.0.as_ref(), c.compare.1.as_ref()],
+    AST::Piecewise(p) => p
+      .piecewise
+      .iter()
+      .flat_map(|(condition, value)| [condition.as_ref(), value.as_ref()])
+      .chain(p.otherwise.as_deref())
+      .collect(),
+    AST::Points(p) => p
+      .points
+      .iter()
+      .flat_map(|(x, y)| [x.as_ref(), y.as_ref()])
+      .collect(),
+    AST::Statement(s) => vec![s.statement.as_ref()],
+    AST::Number(_) => vec![],
+  };
+  children.into_iter().flat_map(variables).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::functions::pretty;
+  use crate::syntax::parser::Parser;
+
+  fn parse(input: &str) -> AST {
+    *Parser::new(input.to_string()).parse_expression().unwrap()
+  }
+
+  // each variable is replaced once, by what it stood for before
+  #[test]
+  fn simultaneous() {
+    let swapped = substitute_all(
+      parse("x^2 + 2y"),
+      &BTreeMap::from([
+        ("x".to_string(), AST::Variable("y".to_string())),
+        ("y".to_string(), parse("x + 1")),
+      ]),
+    );
+    assert_eq!(pretty(swapped), "y^2 + 2 * (x + 1)");
+  }
+}
@@ -0,0 +1,112 @@
+use super::fit::polynomial;
+use super::points::branches;
+use super::round;
+use crate::syntax::parser::{Identity, AST};
+
+// the tangent and normal to one branch of a curve at a point
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tangent {
+  pub point: (f64, f64),
+  pub gradient: f64,
+  pub tangent: AST,
+  pub normal: AST,
+}
+
+// a line written `x = x0`, which cannot be drawn as a curve of y against x
+pub fn vertical(line: &AST) -> bool {
+  matches!(line, AST::Identity(i) if *i.identity[0] == AST::Variable("x".to_string()))
+}
+
+// the line through point with the given gradient as `y = mx + c`, or `x = x0` when it is vertical
+fn line(gradient: f64, point: (f64, f64)) -> AST {
+  let equals = |var: &str, value: AST| {
+    AST::Identity(Identity {
+      identity: vec![Box::new(AST::Variable(var.to_string())), Box::new(value)],
+    })
+  };
+  if !gradient.is_finite() {
+    return equals("x", AST::Number(round(point.0)));
+  }
+  equals("y", polynomial(&[point.1 - gradient * point.0, gradient]))
+}
+
+// the tangent and normal at x to each branch of a solved curve that has a value there. the gradient
+// is found by central differences, so a corner such as `|x|` at 0 reads as flat
+pub fn tangents(ast: &AST, x: f64) -> Vec<Tangent> {
+  if vertical(ast) {
+    return Vec::new();
+  }
+  let h = 1e-5 * x.abs().max(1f64);
+  let (before, at, after) = (branches(ast, x - h), branches(ast, x), branches(ast, x + h));
+  at.iter()
+    .enumerate()
+    .filter(|(_, y)| y.is_finite())
+    .map(|(i, &y)| {
+      let gradient = match (before.get(i), after.get(i)) {
+        (Some(b), Some(a)) => (a - b) / (2f64 * h),
+        _ => f64::NAN,
+      };
+      // a gradient that cannot be found, such as `sqrt(x)` at 0, is taken as vertical
+      let gradient = if gradient.is_finite() {
+        gradient
+      } else {
+        f64::INFINITY
+      };
+      let normal = if gradient == 0f64 {
+        f64::INFINITY
+      } else {
+        -1f64 / gradient
+      };
+      Tangent {
+        point: (x, y),
+        gradient,
+        tangent: line(gradient, (x, y)),
+        normal: line(normal, (x, y)),
+      }
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::functions::pretty;
+  use crate::syntax::parser::Parser;
+
+  fn lines(input: &str, x: f64) -> Vec<(String, String)> {
+    let curve = *Parser::new(input.to_string() + ";").parse().unwrap()[0].clone();
+    tangents(&curve, x)
+      .into_iter()
+      .map(|t| (pretty(t.tangent), pretty(t.normal)))
+      .collect()
+  }
+
+  fn pair(tangent: &str, normal: &str) -> (String, String) {
+    (tangent.to_string(), normal.to_string())
+  }
+
+  // a parabola has one tangent and a circle has one on each half
+  #[test]
+  fn curves() {
+    assert_eq!(
+      lines("y = x^2", 2f64),
+      vec![pair("y = 4x - 4", "y = -0.25 * x + 4.5")]
+    );
+    assert_eq!(
+      lines("y = ±sqrt(25 - x^2)", 3f64),
+      vec![
+        pair("y = -0.75 * x + 6.25", "y = 1.333333x"),
+        pair("y = 0.75x - 6.25", "y = -1.333333 * x"),
+      ]
+    );
+  }
+
+  // a flat tangent has a vertical normal, and the other way round at the edge of a domain
+  #[test]
+  fn vertical_lines() {
+    assert_eq!(lines("y = 3", 1f64), vec![pair("y = 3", "x = 1")]);
+    assert_eq!(lines("y = sqrt(x)", 0f64), vec![pair("x = 0", "y = 0")]);
+    assert!(lines("y = sqrt(x)", -1f64).is_empty());
+    assert!(vertical(&tangents(&AST::Number(3f64), 1f64)[0].normal));
+  }
+}
@@ -0,0 +1,191 @@
+use super::{eval, substitute, variables};
+use crate::syntax::parser::AST;
+
+// how far a root may be out, relative to the size of the values being compared
+pub const TOLERANCE: f64 = 1e-9;
+
+// how well one root satisfies the equation it was solved from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+  pub root: f64,
+  // the difference between the two sides of the original equation at the root
+  pub residual: f64,
+  // how far the root moved when the solution was simplified
+  pub drift: f64,
+}
+
+impl Check {
+  pub fn satisfies(&self) -> bool {
+    self.residual <= TOLERANCE
+  }
+
+  // whether simplifying the solution kept the root where the formula put it
+  pub fn precise(&self) -> bool {
+    self.drift <= TOLERANCE * self.root.abs().max(1f64)
+  }
+
+  pub fn sound(&self) -> bool {
+    self.satisfies() && self.precise()
+  }
+}
+
+fn sides(ast: &AST) -> Option<(&AST, &AST)> {
+  match ast {
+    AST::Statement(s) => sides(&s.statement),
+    AST::Identity(i) if i.identity.len() == 2 => Some((&i.identity[0], &i.identity[1])),
+    _ => None,
+  }
+}
+
+// every value of an expression with no variables left in it
+fn values(ast: &AST) -> Option<Vec<f64>> {
+  if variables(ast).is_empty() {
+    eval(ast.clone(), 0f64).ok()
+  } else {
+    None
+  }
+}
+
+// plug each root of a solution back into the equation it came from, and compare it with the root
+// the solution had before it was simplified. None when the roots are not plain numbers, and an
+// error when the solution still contains var, since then it is no solution at all
+pub fn verify(
+  original: &AST,
+  var: &str,
+  solved: &AST,
+  solution: &AST,
+) -> Result<Option<Vec<Check>>, String> {
+  match sides(solution) {
+    Some((_, right)) if variables(right).contains(var) => Err(format!(
+      "{} is still on the right hand side, so it was not solved for",
+      var
+    )),
+    _ => Ok(checks(original, var, solved, solution)),
+  }
+}
+
+fn checks(original: &AST, var: &str, solved: &AST, solution: &AST) -> Option<Vec<Check>> {
+  let (left, right) = sides(original)?;
+  let formula = values(sides(solved)?.1)?;
+  let roots = values(sides(solution)?.1)?;
+  let mut checks = Vec::new();
+  for (i, root) in roots.into_iter().enumerate() {
+    if !root.is_finite() {
+      continue;
+    }
+    let at = |side: &AST| values(&substitute(side.clone(), var, &AST::Number(root)));
+    let (left, right) = (at(left)?, at(right)?);
+    // the residual is relative to the size of the sides, so large coefficients are not held to
+    // a tighter standard than small ones
+    let residual = left
+      .iter()
+      .flat_map(|l| {
+        right
+          .iter()
+          .map(move |r| (l - r).abs() / l.abs().max(r.abs()).max(1f64))
+      })
+      .fold(f64::INFINITY, f64::min);
+    let drift = formula.get(i).map_or(0f64, |f| (f - root).abs());
+    checks.push(Check {
+      root,
+      residual,
+      drift,
+    });
+  }
+  Some(checks)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::functions::{expand, quadratic::Quadratic};
+  use crate::syntax::parser::{Expr, Identity, Index, Sign, Term};
+  use proptest::prelude::*;
+
+  fn check(ast: AST, var: &str) -> Option<Vec<Check>> {
+    let solved = Quadratic::from(expand(ast.clone()), var.to_string())
+      .unwrap()
+      .solve()
+      .unwrap();
+    verify(&ast, var, &solved, &expand(solved.clone())).unwrap()
+  }
+
+  fn parse(input: &str) -> AST {
+    *crate::syntax::parser::Parser::new(input.to_string() + ";")
+      .parse()
+      .unwrap()[0]
+      .clone()
+  }
+
+  // roots that satisfy the equation pass, and a wrong one is caught
+  #[test]
+  fn residuals() {
+    let checks = check(parse("2x^2 + 2x = 12"), "x").unwrap();
+    assert_eq!(
+      checks.iter().map(|c| c.root).collect::<Vec<f64>>(),
+      vec![2f64, -3f64]
+    );
+    assert!(checks.iter().all(Check::sound));
+
+    let wrong = parse("x = 3");
+    let checks = verify(&parse("x^2 = 4"), "x", &wrong, &wrong)
+      .unwrap()
+      .unwrap();
+    assert_eq!(checks[0].residual, 5f64 / 9f64);
+    assert!(!checks[0].sound());
+
+    assert_eq!(check(parse("y = x^2 + 1"), "y"), None);
+  }
+
+  // a solution with var on both sides is a failed check, not one that cannot be made
+  #[test]
+  fn unsolved() {
+    let unsolved = parse("x = -8 + x^3");
+    assert!(verify(&parse("x^3 = 8"), "x", &unsolved, &unsolved).is_err());
+  }
+
+  // `a x^2 + b x + c = 0`, written the way the parser builds it
+  fn quadratic(a: f64, b: f64, c: f64) -> AST {
+    let x = || Box::new(AST::Variable("x".to_string()));
+    AST::Identity(Identity {
+      identity: vec![
+        Box::new(AST::Expr(Expr {
+          sign: Sign::Add,
+          expr: vec![
+            Box::new(AST::Term(Term {
+              sign: Sign::Mul,
+              term: vec![
+                Box::new(AST::Number(a)),
+                Box::new(AST::Index(Index {
+                  sign: Sign::Pow,
+                  index: (x(), Box::new(AST::Number(2f64))),
+                })),
+              ],
+            })),
+            Box::new(AST::Term(Term {
+              sign: Sign::Mul,
+              term: vec![Box::new(AST::Number(b)), x()],
+            })),
+            Box::new(AST::Number(c)),
+          ],
+        })),
+        Box::new(AST::Number(0f64)),
+      ],
+    })
+  }
+
+  proptest! {
+    // every real root of every quadratic satisfies it, and there are as many as the discriminant says
+    #[test]
+    fn coefficients(a in -20i32..20, b in -20i32..20, c in -20i32..20) {
+      prop_assume!(a != 0);
+      let (a, b, c) = (a as f64, b as f64, c as f64);
+      let checks = check(quadratic(a, b, c), "x").unwrap();
+      let discriminant = b * b - 4f64 * a * c;
+      prop_assert_eq!(checks.len(), if discriminant < 0f64 { 0 } else { 2 });
+      for check in checks {
+        prop_assert!(check.sound(), "{:?}", check);
+      }
+    }
+  }
+}
@@ -0,0 +1,234 @@
+//! Parse, simplify, solve and draw equations in one variable.
+//!
+//! ```
+//! let statements = graphing_calculator::parse("2y = 4x + 6; @draw").unwrap();
+//! let solved = graphing_calculator::solve(&statements[0], "y").unwrap();
+//! assert_eq!(graphing_calculator::eval(&solved, 1.0), Ok(vec![5.0]));
+//! ```
+//!
+//! The command line calculator is built on top of this library, behind the default `cli` feature.
+//! Turn default features off to use the library without it.
+#![allow(clippy::vec_box, clippy::upper_case_acronyms)]
+
+mod functions;
+mod syntax;
+
+use std::path::Path;
+
+pub use functions::linear::{LinearSystem, Rational, Solution};
+pub use functions::quadratic::Quadratic;
+pub use functions::{
+  eliminate, expand, fit, intercepts, intersections, pretty, substitute, tangents, to_latex,
+  to_mathml, turning_points, unique, variables, Check, Dash, Elimination, Figure, Fit, Format,
+  Model, Panel, Step, Style, Tangent, Theme,
+};
+pub use syntax::lexer::{HumanPosition, Position, SyntaxError, SyntaxWarning};
+pub use syntax::parser::{
+  Argument, Call, Command, Compare, Expr, Identity, Index, Parser, Piecewise, Points, Relation,
+  Sign, Statement, Term, Unary, AST,
+};
+pub use syntax::schema::Document;
+
+/// Anything that stops the library from giving an answer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+  /// The input could not be parsed.
+  Syntax(SyntaxError),
+  /// A variable with no value was found while evaluating.
+  UnknownVariable(String),
+  /// Only an equation with two sides can be solved.
+  NotAnEquation,
+  /// The variable to solve for does not appear in the equation.
+  MissingVariable(String),
+  /// The equation has a term in the variable that is not a power of it up
+  /// to 2, such as `x^3` or `sin(x)`.
+  NotQuadratic(String),
+  /// The image could not be drawn or written.
+  Draw(String),
+}
+
+impl std::fmt::Display for Error {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      Error::Syntax(e) => write!(f, "{}", e),
+      Error::UnknownVariable(v) => write!(f, "Unknown variable {}", v),
+      Error::NotAnEquation => write!(f, "Expected an equation with two sides"),
+      Error::MissingVariable(v) => write!(f, "The equation does not contain {}", v),
+      Error::NotQuadratic(v) => write!(f, "The equation is not quadratic in {}", v),
+      Error::Draw(e) => write!(f, "Could not draw: {}", e),
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+impl From<SyntaxError> for Error {
+  fn from(e: SyntaxError) -> Error {
+    Error::Syntax(e)
+  }
+}
+
+// the equation inside a statement, leaving its command behind
+fn equation(ast: &AST) -> &AST {
+  match ast {
+    AST::Statement(s) => &s.statement,
+    ast => ast,
+  }
+}
+
+/// Parse the statements of a `.graph` file, each an equation ending in `;`
+/// with any `@commands` after it.
+pub fn parse(input: &str) -> Result<Vec<AST>, Error> {
+  let statements = Parser::new(input.to_string()).parse()?;
+  Ok(statements.into_iter().map(|s| *s).collect())
+}
+
+/// Parse a single expression with no `=` or `;`, such as `2x + 1`.
+pub fn parse_expression(input: &str) -> Result<AST, Error> {
+  Ok(*Parser::new(input.to_string()).parse_expression()?)
+}
+
+/// Every value of an expression at x, two when it contains a `±`. An equation
+/// is evaluated by its right hand side, so `y = 2x` at 3 is 6.
+pub fn eval(ast: &AST, x: f64) -> Result<Vec<f64>, Error> {
+  let expression = match equation(ast) {
+    AST::Identity(identity) => identity.identity.last().ok_or(Error::NotAnEquation)?,
+    ast => ast,
+  };
+  functions::eval(expression.clone(), x)
+}
+
+/// Each step of solving an equation for a variable, as `solve` takes them.
+#[derive(Debug, Clone)]
+pub struct Working {
+  /// The equation that was solved.
+  pub original: AST,
+  /// The variable it was solved for.
+  pub var: String,
+  /// The equation with its brackets expanded and like terms collected.
+  pub expanded: AST,
+  /// The coefficients of the equation as a quadratic in the variable.
+  pub quadratic: Quadratic,
+  /// `var = ...` straight from the quadratic formula.
+  pub solved: AST,
+  /// The solved equation simplified, which is what `solve` gives.
+  pub solution: AST,
+}
+
+impl Working {
+  /// The values of the variable, or None when the solution depends on other variables.
+  pub fn roots(&self) -> Option<Vec<f64>> {
+    match equation(&self.solution) {
+      AST::Identity(identity) if variables(&identity.identity[1]).is_empty() => Some(
+        functions::eval(*identity.identity[1].clone(), 0f64)
+          .ok()?
+          .into_iter()
+          .filter(|n| n.is_finite())
+          .collect(),
+      ),
+      _ => None,
+    }
+  }
+
+  /// Put each root back into the original equation. None when the roots are not plain numbers,
+  /// and an error when the solution still contains the variable.
+  pub fn verify(&self) -> Result<Option<Vec<Check>>, String> {
+    functions::verify(&self.original, &self.var, &self.solved, &self.solution)
+  }
+}
+
+// only an equation that contains var can be solved for it
+fn solvable(ast: &AST, var: &str) -> Result<(), Error> {
+  match equation(ast) {
+    AST::Identity(identity) if identity.identity.len() == 2 => {}
+    _ => return Err(Error::NotAnEquation),
+  }
+  if !variables(ast).contains(var) {
+    return Err(Error::MissingVariable(var.to_string()));
+  }
+  Ok(())
+}
+
+/// Solve an equation of at most second order for var, keeping each step on the way.
+pub fn working(ast: &AST, var: &str) -> Result<Working, Error> {
+  solvable(ast, var)?;
+  let expanded = expand(ast.clone());
+  let quadratic = Quadratic::from(expanded.clone(), var.to_string())
+    .map_err(|_| Error::NotQuadratic(var.to_string()))?;
+  let solved = quadratic
+    .solve()
+    .map_err(|_| Error::MissingVariable(var.to_string()))?;
+  Ok(Working {
+    original: ast.clone(),
+    var: var.to_string(),
+    expanded,
+    quadratic,
+    solution: expand(solved.clone()),
+    solved,
+  })
+}
+
+/// Solve an equation of at most second order for var, giving `var = ...`.
+pub fn solve(ast: &AST, var: &str) -> Result<AST, Error> {
+  Ok(working(ast, var)?.solution)
+}
+
+/// Solve a statement for y, to draw it as a curve of y against x. A statement
+/// with no y, such as `x = 3`, is solved for x instead, and drawn as the
+/// vertical lines where it holds.
+pub fn curve(ast: &AST) -> Result<Working, Error> {
+  let working = match working(ast, "y") {
+    Err(Error::MissingVariable(_)) => working(ast, "x")?,
+    working => working?,
+  };
+  // every other variable needs a value before the curve can be drawn
+  eval(&working.solution, 0f64)?;
+  Ok(working)
+}
+
+/// Each rule applied on the way to the answer `solve` gives, for a worked solution.
+pub fn steps(ast: &AST, var: &str) -> Result<Vec<Step>, Error> {
+  solvable(ast, var)?;
+  functions::steps(ast.clone(), var).map_err(|_| Error::NotQuadratic(var.to_string()))
+}
+
+/// Draw each equation as a curve of y against x, in the format and viewport of
+/// the figure, styled by any `color`, `style`, `width` and `label` options of
+/// the commands of its statement.
+pub fn draw(file: &Path, title: &str, equations: &[AST], figure: &Figure) -> Result<(), Error> {
+  let curves = equations
+    .iter()
+    .map(|ast| {
+      let solved = curve(ast)?.solution;
+      let style = match ast {
+        AST::Statement(s) => Style::from(&s.commands).map_err(Error::Draw)?,
+        _ => Style::default(),
+      };
+      let label = style
+        .label
+        .clone()
+        .unwrap_or_else(|| functions::pretty(expand(ast.clone())));
+      Ok((solved, label, style))
+    })
+    .collect::<Result<Vec<(AST, String, Style)>, Error>>()?;
+  let panel = Panel {
+    title: None,
+    x: figure.x,
+    y: figure.y,
+    curves,
+    points: Vec::new(),
+  };
+  draw_panels(file, title, vec![panel], figure)
+}
+
+/// Draw each panel in a grid under the title, or a lone panel filling the
+/// figure, with the curves and points already worked out.
+pub fn draw_panels(
+  file: &Path,
+  title: &str,
+  panels: Vec<Panel>,
+  figure: &Figure,
+) -> Result<(), Error> {
+  functions::draw(&file.to_string_lossy().to_string(), panels, title, figure)
+    .map_err(|e| Error::Draw(e.to_string()))
+}
@@ -1,177 +1,260 @@
 mod cmd;
-mod functions;
-mod syntax;
+mod repl;
+mod trace;
 
-use cmd::run;
-use functions::{draw, eval, expand, pretty, quadratic::Quadratic};
-use syntax::parser::{Identity, Parser, AST};
+use clap::Parser as _;
+use cmd::{Cli, Command, OutputFormat};
+use graphing_calculator::{
+    eliminate, variables, Document, HumanPosition, LinearSystem, Parser, Position, SyntaxError, AST,
+};
+use serde_json::json;
+use trace::{expression, format_points, warn, Format, Output, Plot};
 
-use std::env;
+use std::error::Error;
 use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    fn graph(args: Vec<String>) {
-        let mut parser = Parser::new(fs::read_to_string(args[0].clone()).unwrap());
-        let ast = parser.parse();
-        let mut draws: Vec<(AST, String)> = Vec::new();
-        for statement in ast {
-            println!("original:  {}", pretty(*statement.clone()));
-            match *statement {
-                AST::Statement(a) => {
-                    if a.clone().command.unwrap_or(String::new()) == "draw".to_string() {
-                        let expr = expand(AST::Statement(a));
-                        let label = pretty(expr.clone());
-                        println!("expanded:  {}", label);
-                        let quad = Quadratic::from(expr, "y".to_string());
-                        println!(
-                            "quadratic: \n  a: {}\n  b: {}\n  c: {}",
-                            pretty(quad.a.clone()),
-                            pretty(quad.b.clone()),
-                            pretty(quad.c.clone())
-                        );
-                        let expr = quad.solve();
-                        println!("solved:    {}", pretty(expr.clone()));
-                        let expr = expand(expr);
-                        println!("expanded:  {}", pretty(expr.clone()));
-                        draws.push((expr, label));
-                        println!("");
-                    }
-                }
-                _ => {}
-            }
+// parse a single equation given on the command line
+fn equation(input: &str) -> Result<AST, SyntaxError> {
+    let mut parser = Parser::new(input.to_string() + ";");
+    let statements = parser.parse()?;
+    warn(&parser);
+    match statements.into_iter().next() {
+        Some(ast) => Ok(*ast),
+        None => Err(SyntaxError {
+            message: "SyntaxError: Expected an equation".to_string(),
+            position: Position {
+                human: HumanPosition { line: 1, column: 1 },
+                machine: 0,
+            },
+            incomplete: true,
+        }),
+    }
+}
+
+// the statements of a .graph file, or of a document already parsed to json
+fn statements(input: &Path) -> Result<Vec<AST>, Box<dyn Error>> {
+    let text = fs::read_to_string(input)?;
+    if input.extension().is_some_and(|e| e == "json") {
+        return Ok(Document::from_json(&text)?.statements);
+    }
+    let mut parser = Parser::new(text);
+    let statements = parser.parse()?;
+    warn(&parser);
+    Ok(statements.into_iter().map(|s| *s).collect())
+}
+
+fn graph(
+    input: &Path,
+    output: &Path,
+    title: &str,
+    mut plot: Plot,
+    out: &Output,
+) -> Result<(), Box<dyn Error>> {
+    for statement in statements(input)? {
+        out.ast("original", &statement);
+        if let AST::Statement(a) = statement {
+            plot.statement(a, out)?;
         }
-        draw(&args[1].clone(), draws, &args[2].clone()).expect("Failed to draw.");
     }
+    let document = plot.draw(&output.display().to_string(), title, out)?;
+    out.document(&document);
+    Ok(())
+}
 
-    fn solve(args: Vec<String>) {
-        let mut parser = Parser::new(args[0].clone() + ";");
-        let ast = parser.parse();
-        println!("original:  {}", pretty(*ast[0].clone()));
-        match *ast[0].clone() {
-            AST::Statement(a) => {
-                let expr = expand(AST::Statement(a));
-                let label = pretty(expr.clone());
-                println!("expanded:  {}", label);
-                let quad = Quadratic::from(expr, args[1].clone());
-                println!(
-                    "quadratic: \n  a: {}\n  b: {}\n  c: {}",
-                    pretty(quad.a.clone()),
-                    pretty(quad.b.clone()),
-                    pretty(quad.c.clone())
-                );
-                let expr = quad.solve();
-                println!("solved:    {}", pretty(expr.clone()));
-                let expr = expand(expr);
-                println!("expanded:  {}", pretty(expr.clone()));
-            }
-            _ => {}
+fn solve(
+    equation: AST,
+    var: String,
+    steps: bool,
+    verify: bool,
+    out: &Output,
+) -> Result<(), Box<dyn Error>> {
+    out.ast("original", &equation);
+    let worked = if steps {
+        graphing_calculator::steps(&equation, &var)?
+    } else {
+        Vec::new()
+    };
+    out.worked(&worked);
+    // the worked steps replace the usual working
+    let quiet = Output {
+        quiet: true,
+        format: out.format,
+    };
+    let working = trace::solve(&equation, &var, if steps { &quiet } else { out })?;
+    out.result("solution", &out.render(&working.solution));
+    let mut document = trace::document(&working);
+    document["variable"] = json!(var);
+    if steps {
+        document["steps"] = trace::worked(&worked);
+    }
+    if !verify {
+        out.document(&document);
+        return Ok(());
+    }
+    let checks = working.verify();
+    out.verified(&var, &checks);
+    document["verification"] = trace::verification(&checks);
+    out.document(&document);
+    match checks {
+        Ok(Some(checks)) if !checks.iter().all(|check| check.sound()) => {
+            Err("verification failed, a root does not hold".into())
         }
+        Err(e) => Err(format!("verification failed, {}", e).into()),
+        _ => Ok(()),
     }
+}
 
-    fn simultaneous(args: Vec<String>) {
-        let mut expr1: Option<AST> = None;
-        let mut parser = Parser::new(args[0].clone() + ";");
-        let ast = parser.parse();
-        println!("original:  {}", pretty(*ast[0].clone()));
-        match *ast[0].clone() {
-            AST::Statement(a) => {
-                let expr = expand(AST::Statement(a));
-                let label = pretty(expr.clone());
-                println!("expanded:  {}", label);
-                let quad = Quadratic::from(expr, "y".to_string());
-                println!(
-                    "quadratic: \n  a: {}\n  b: {}\n  c: {}",
-                    pretty(quad.a.clone()),
-                    pretty(quad.b.clone()),
-                    pretty(quad.c.clone())
-                );
-                let expr = quad.solve();
-                println!("solved:    {}", pretty(expr.clone()));
-                expr1 = Some(expand(expr));
-                println!("expanded:  {}", pretty(expr1.clone().unwrap()));
-                println!("");
+// solve a pair of equations by putting one into the other, eliminating whichever variable one of
+// them is linear in
+fn substitution(equations: Vec<AST>, out: &Output) -> Result<(), Box<dyn Error>> {
+    let [first, second] = &equations[..] else {
+        return Err("Expected two equations.".into());
+    };
+    for ast in equations.iter() {
+        out.ast("original", ast);
+    }
+    let names: Vec<String> = variables(first)
+        .union(&variables(second))
+        .cloned()
+        .collect();
+    // y first, so a curve written as `y = ...` is put into the other
+    let mut elimination = None;
+    let mut error = None;
+    for var in names.iter().rev() {
+        match eliminate(first, second, var) {
+            Ok(e) => {
+                elimination = Some(e);
+                break;
+            }
+            Err(e) => {
+                error.get_or_insert(e);
             }
-            _ => {}
         }
+    }
+    let elimination =
+        elimination.ok_or_else(|| error.unwrap_or("Expected two variables.".to_string()))?;
+    out.ast("solved", &elimination.solved);
+    out.ast("combined", &elimination.substituted);
+    out.quadratic(&elimination.quadratic);
+    out.ast("solution", &elimination.solution);
+    let points: Vec<(f64, f64)> = elimination
+        .points
+        .iter()
+        .map(|p| (p[&names[0]], p[&names[1]]))
+        .collect();
+    out.result("points", &format_points(&points));
+    out.gap();
+    out.document(&json!({
+      "method": "substitution",
+      "equations": equations.iter().map(expression).collect::<Vec<_>>(),
+      "variables": names,
+      "eliminated": elimination.var,
+      "remaining": elimination.remaining,
+      "solved": expression(&elimination.solved),
+      "substituted": expression(&elimination.substituted),
+      "quadratic": {
+        "a": expression(&elimination.quadratic.a),
+        "b": expression(&elimination.quadratic.b),
+        "c": expression(&elimination.quadratic.c),
+      },
+      "solution": expression(&elimination.solution),
+      "points": points,
+    }));
+    Ok(())
+}
 
-        let mut expr2: Option<AST> = None;
-        let mut parser = Parser::new(args[1].clone() + ";");
-        let ast = parser.parse();
-        println!("original:  {}", pretty(*ast[0].clone()));
-        match *ast[0].clone() {
-            AST::Statement(a) => {
-                let expr = expand(AST::Statement(a));
-                let label = pretty(expr.clone());
-                println!("expanded:  {}", label);
-                let quad = Quadratic::from(expr, "y".to_string());
-                println!(
-                    "quadratic: \n  a: {}\n  b: {}\n  c: {}",
-                    pretty(quad.a.clone()),
-                    pretty(quad.b.clone()),
-                    pretty(quad.c.clone())
-                );
-                let expr = quad.solve();
-                println!("solved:    {}", pretty(expr.clone()));
-                expr2 = Some(expand(expr));
-                println!("expanded:  {}", pretty(expr2.clone().unwrap()));
-                println!("");
+fn simultaneous(equations: Vec<AST>, out: &Output) -> Result<(), Box<dyn Error>> {
+    // the exact elimination can overflow, which two equations can still recover from
+    let solved = LinearSystem::from(equations.clone())
+        .and_then(|system| system.solve().map(|solution| (system, solution)));
+    match solved {
+        Ok((system, solution)) => {
+            for equation in equations.iter() {
+                out.ast("original", equation);
             }
-            _ => {}
+            out.step("variables", &system.variables.join(", "));
+            out.step("matrix", &format!("\n{}", system));
+            out.result("solution", &solution.to_string());
+            out.gap();
+            out.document(&json!({
+              "method": "linear",
+              "equations": equations.iter().map(expression).collect::<Vec<_>>(),
+              "variables": system.variables,
+              "matrix": system.rows,
+              "solution": solution.json(),
+            }));
+            Ok(())
         }
+        Err(_) if equations.len() == 2 => substitution(equations, out),
+        Err(e) => Err(e.into()),
+    }
+}
 
-        let expr = AST::Identity(Identity {
-            identity: vec![
-                match expr1.clone().unwrap() {
-                    AST::Identity(a) => a.identity[1].clone(),
-                    _ => panic!("Expected identity."),
-                },
-                match expr2.unwrap() {
-                    AST::Identity(a) => a.identity[1].clone(),
-                    _ => panic!("Expected identity."),
-                },
-            ],
-        });
-        println!("original: {}", pretty(expr.clone()));
-        let quad = Quadratic::from(expr, "x".to_string());
-        println!(
-            "quadratic: \n  a: {}\n  b: {}\n  c: {}",
-            pretty(quad.a.clone()),
-            pretty(quad.b.clone()),
-            pretty(quad.c.clone())
-        );
-        let expr = quad.solve();
-        println!("solved:    {}", pretty(expr.clone()));
-        let expr = expand(expr);
-        println!("expanded:  {}", pretty(expr.clone()));
-        let xs = eval(expr, 0f64);
-        println!("evaluated: x = {:?}", xs.clone());
-        let mut ys: Vec<f64> = Vec::new();
-        for x in xs.clone() {
-            ys.extend(eval(expr1.clone().unwrap(), x));
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let out = Output {
+        quiet: cli.quiet,
+        format: match cli.format {
+            OutputFormat::Text => Format::Text,
+            OutputFormat::Latex => Format::Latex,
+            OutputFormat::Json => Format::Json,
+        },
+    };
+    match cli.command {
+        Command::Draw {
+            input,
+            output,
+            title,
+            figure,
+        } => {
+            let title = title.unwrap_or_else(|| {
+                input
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            });
+            let plot = Plot::new(figure.figure(Some(&output)));
+            graph(&input, &output, &title, plot, &out)
+        }
+        Command::Parse { input, output } => {
+            let document = Document::new(statements(&input)?);
+            match output {
+                Some(output) => fs::write(output, document.to_json())?,
+                None => println!("{}", document.to_json()),
+            }
+            Ok(())
+        }
+        Command::Solve {
+            equation: e,
+            var,
+            steps,
+            verify,
+        } => solve(equation(&e)?, var, steps, verify, &out),
+        Command::Simultaneous { equations } => simultaneous(
+            equations
+                .iter()
+                .map(|e| equation(e))
+                .collect::<Result<Vec<AST>, SyntaxError>>()?,
+            &out,
+        ),
+        Command::Repl { .. } if out.format == Format::Json => {
+            Err("the repl only writes text".into())
+        }
+        Command::Repl { figure } => {
+            repl::repl(Plot::new(figure.figure(None)), &out);
+            Ok(())
         }
-        println!("evaluated: y = {:?}", ys);
-        let mut i = 0;
-        println!(
-            "points: {}",
-            xs.iter()
-                .map(|x| {
-                    i += 1;
-                    format!("({:.3}, {:.3})", x, ys[i - 1])
-                })
-                .collect::<Vec<String>>()
-                .join(", ")
-        );
-        println!("");
     }
+}
 
-    run(
-        args,
-        vec![
-            "draw".to_string(),
-            "solve".to_string(),
-            "simultaneous".to_string(),
-        ],
-        vec![&graph, &solve, &simultaneous],
-    );
+// run the command given on the command line, reporting any error before exiting
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,349 @@
+use crate::trace::{self, warn, Output, Plot};
+use graphing_calculator::{
+  expand, steps, substitute, Parser, Statement, SyntaxError, Working, AST,
+};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::error::Error;
+
+const HELP: &str = "Statements end with a semicolon and may be followed by a command:
+    a = 3;                    define a name for the rest of the session
+    y = a x + 1; @draw        add a curve to the plot (also @intercepts, @turning)
+    y = x^2; @tangent 2       add a curve with its tangent and normal at x = 2
+    points: (1, 2), (2, 4.1); @fit linear
+                              plot measured points with a fitted curve (also quadratic, exp)
+    y = x; @draw color=#f00 style=dashed width=2 label=\"cost\"
+                              style a curve (also solid, dotted), after any command
+    y = x^2; @turning @tangent 1
+                              give a statement several commands
+    y = x^2; @panel \"squares\" x=-5..5 @draw
+                              draw the statements from here on in a panel of their own
+  Commands:
+    :solve <equation> [for <var>]
+    :steps <equation> [for <var>]  solve, showing each rule applied
+    :expand <expression>
+    :plot [out file (.svg)]
+    :help
+    :quit";
+
+// the definitions and curves kept between lines
+struct Session {
+  definitions: Vec<(String, AST)>,
+  plot: Plot,
+  // the lines of a statement still being typed
+  buffer: String,
+}
+
+impl Session {
+  fn new(plot: Plot) -> Session {
+    Session {
+      definitions: Vec::new(),
+      plot,
+      buffer: String::new(),
+    }
+  }
+
+  // replace every defined name with its value
+  fn resolve(&self, ast: AST) -> AST {
+    self
+      .definitions
+      .iter()
+      .fold(ast, |ast, (name, value)| substitute(ast, name, value))
+  }
+
+  // a statement like `a = 3;` defines a name, anything but x or y
+  fn define(&mut self, statement: &Statement, out: &Output) -> bool {
+    if !statement.commands.is_empty() {
+      return false;
+    }
+    let identity = match *statement.statement.clone() {
+      AST::Identity(identity) if identity.identity.len() == 2 => identity,
+      _ => return false,
+    };
+    let name = match *identity.identity[0].clone() {
+      AST::Variable(name) if name != "x" && name != "y" => name,
+      _ => return false,
+    };
+    let value = expand(self.resolve(*identity.identity[1].clone()));
+    out.result("defined", &format!("{} = {}", name, out.render(&value)));
+    self.definitions.retain(|(n, _)| *n != name);
+    self.definitions.push((name, value));
+    true
+  }
+
+  fn statement(&mut self, statement: Statement, out: &Output) {
+    out.ast("original", &AST::Statement(statement.clone()));
+    if self.define(&statement, out) {
+      return;
+    }
+    let mut resolve = |a: Box<AST>| Box::new(self.resolve(*a));
+    let statement = Statement {
+      statement: resolve(statement.statement),
+      commands: statement
+        .commands
+        .into_iter()
+        .map(|c| c.map(&mut resolve))
+        .collect(),
+    };
+    match self.plot.statement(statement.clone(), out) {
+      Ok(true) => {}
+      Ok(false) => out.result("expanded", &out.render(&expand(AST::Statement(statement)))),
+      Err(e) => out.result("draw", &e.to_string()),
+    }
+  }
+
+  // solve each equation in `<equation> [for <var>]`, with defined names replaced
+  fn solve(&self, rest: &str, out: &Output) -> Result<Vec<Working>, Box<dyn Error>> {
+    let (equation, var) = rest.rsplit_once(" for ").unwrap_or((rest, "x"));
+    let mut parser = Parser::new(equation.to_string() + ";");
+    let statements = parser.parse()?;
+    warn(&parser);
+    let mut workings = Vec::new();
+    for ast in statements {
+      out.ast("original", &ast);
+      let working = trace::solve(&self.resolve(*ast), var.trim(), out)?;
+      out.result("solution", &out.render(&working.solution));
+      workings.push(working);
+    }
+    Ok(workings)
+  }
+
+  fn expand(&self, rest: &str, out: &Output) -> Result<AST, SyntaxError> {
+    let mut parser = Parser::new(rest.to_string());
+    let ast = parser.parse_expression()?;
+    warn(&parser);
+    out.ast("original", &ast);
+    let expanded = expand(self.resolve(*ast));
+    out.result("expanded", &out.render(&expanded));
+    Ok(expanded)
+  }
+
+  // run a `:command`, returning false when the session should end
+  fn command(&mut self, line: &str, out: &Output) -> Result<bool, Box<dyn Error>> {
+    let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+    match command {
+      ":solve" => {
+        self.solve(rest, out)?;
+      }
+      ":steps" => {
+        let (equation, var) = rest.rsplit_once(" for ").unwrap_or((rest, "x"));
+        let mut parser = Parser::new(equation.to_string() + ";");
+        let statements = parser.parse()?;
+        warn(&parser);
+        for ast in statements {
+          out.ast("original", &ast);
+          out.worked(&steps(&self.resolve(*ast), var.trim())?);
+        }
+      }
+      ":expand" => {
+        self.expand(rest, out)?;
+      }
+      ":plot" => {
+        let file = if rest.trim().is_empty() {
+          "repl.svg".to_string()
+        } else {
+          rest.trim().to_string()
+        };
+        match self.plot.draw(&file, "repl", out) {
+          Ok(_) => out.result("plotted", &file),
+          Err(e) => eprintln!("Failed to draw: {}", e),
+        }
+      }
+      ":help" => println!("{}", HELP),
+      ":quit" => return Ok(false),
+      _ => eprintln!("Command not found, try :help."),
+    }
+    Ok(true)
+  }
+
+  // take one line of input, returning false when the session should end
+  fn line(&mut self, line: &str, out: &Output) -> bool {
+    if self.buffer.is_empty() && line.trim_start().starts_with(':') {
+      return match self.command(line.trim(), out) {
+        Ok(running) => running,
+        Err(e) => {
+          eprintln!("{}", e);
+          true
+        }
+      };
+    }
+
+    // statements can span several lines, so keep reading until one is complete
+    self.buffer += line;
+    self.buffer += "\n";
+    let mut parser = Parser::new(self.buffer.clone());
+    match parser.parse() {
+      Ok(statements) => {
+        warn(&parser);
+        for statement in statements {
+          if let AST::Statement(s) = *statement {
+            self.statement(s, out);
+          }
+        }
+      }
+      Err(e) if e.incomplete => return true,
+      Err(e) => eprintln!("{}", e),
+    }
+    self.buffer.clear();
+    true
+  }
+}
+
+pub fn repl(plot: Plot, out: &Output) {
+  let mut editor = match DefaultEditor::new() {
+    Ok(editor) => editor,
+    Err(e) => return eprintln!("{}", e),
+  };
+  let history = std::env::var("HOME")
+    .map(|home| home + "/.graphing_calculator_history")
+    .unwrap_or(".graphing_calculator_history".to_string());
+  let _ = editor.load_history(&history);
+
+  let mut session = Session::new(plot);
+  loop {
+    let prompt = if session.buffer.is_empty() {
+      "> "
+    } else {
+      "... "
+    };
+    let line = match editor.readline(prompt) {
+      Ok(line) => line,
+      Err(ReadlineError::Interrupted) => {
+        session.buffer.clear();
+        continue;
+      }
+      Err(ReadlineError::Eof) => break,
+      Err(e) => {
+        eprintln!("{}", e);
+        break;
+      }
+    };
+    if line.trim().is_empty() && session.buffer.is_empty() {
+      continue;
+    }
+    let _ = editor.add_history_entry(line.as_str());
+    if !session.line(&line, out) {
+      break;
+    }
+  }
+  let _ = editor.save_history(&history);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::trace::Format;
+  use graphing_calculator::{pretty, Figure};
+
+  fn session() -> (Session, Output) {
+    (
+      Session::new(Plot::new(Figure::default())),
+      Output {
+        quiet: true,
+        format: Format::Text,
+      },
+    )
+  }
+
+  // a definition is kept, expanded, and replaces an earlier one of the same name
+  #[test]
+  fn definitions() {
+    let (mut session, out) = session();
+    assert!(session.line("a = 1 + 2;", &out));
+    assert!(session.line("b = 2a;", &out));
+    assert!(session.line("a = 4;", &out));
+    assert_eq!(
+      session
+        .definitions
+        .iter()
+        .map(|(name, value)| format!("{} = {}", name, pretty(value.clone())))
+        .collect::<Vec<String>>(),
+      vec!["b = 6", "a = 4"]
+    );
+  }
+
+  // x and y are never defined, so `y = ...` is left for the plot
+  #[test]
+  fn resolve() {
+    let (mut session, out) = session();
+    session.line("a = 3;", &out);
+    session.line("y = a x;", &out);
+    assert_eq!(session.definitions.len(), 1);
+    let ast = Parser::new("a x + c".to_string())
+      .parse_expression()
+      .unwrap();
+    assert_eq!(pretty(super::expand(session.resolve(*ast))), "x * 3 + c");
+  }
+
+  // :solve uses the defined names and reads an optional variable
+  #[test]
+  fn solve() {
+    let (mut session, out) = session();
+    session.line("a = 2;", &out);
+    let workings = session.solve("y = a t - 4 for t", &out).unwrap();
+    assert_eq!(workings.len(), 1);
+    assert_eq!(workings[0].roots(), None);
+    let workings = session.solve("a x = 4", &out).unwrap();
+    assert_eq!(workings[0].roots(), Some(vec![2f64]));
+    assert!(session.line(":solve x = 1", &out));
+    assert!(session.solve("x = (", &out).is_err());
+    assert!(session.solve("x^3 = 8", &out).is_err());
+  }
+
+  // :expand uses the defined names
+  #[test]
+  fn expand() {
+    let (mut session, out) = session();
+    session.line("a = 2;", &out);
+    assert_eq!(
+      pretty(session.expand("(x + a)^2", &out).unwrap()),
+      "(2 + x)^2"
+    );
+  }
+
+  // a curve with a name that has no value is reported and left out, and the session goes on
+  #[test]
+  fn unknown() {
+    let (mut session, out) = session();
+    assert!(session.line("y = b x; @intercepts", &out));
+    assert!(session.plot.draws.is_empty());
+    assert!(session.plot.points.is_empty());
+    session.line("b = 2;", &out);
+    assert!(session.line("y = b x; @intercepts", &out));
+    assert_eq!(session.plot.points, vec![(0f64, 0f64)]);
+  }
+
+  // drawn statements collect in the plot, which :plot writes out
+  #[test]
+  fn plot() {
+    let (mut session, out) = session();
+    session.line("a = 2;", &out);
+    session.line("y = a x; @draw", &out);
+    assert_eq!(session.plot.draws.len(), 1);
+    let file = std::env::temp_dir().join("graphing_calculator_repl.svg");
+    let _ = std::fs::remove_file(&file);
+    assert!(session.line(&format!(":plot {}", file.display()), &out));
+    assert!(file.exists());
+    let _ = std::fs::remove_file(&file);
+  }
+
+  // an incomplete statement is held until a later line completes it
+  #[test]
+  fn buffer() {
+    let (mut session, out) = session();
+    assert!(session.line("a = (1 +", &out));
+    assert_eq!(session.buffer, "a = (1 +\n");
+    assert!(session.definitions.is_empty());
+    assert!(session.line("2);", &out));
+    assert!(session.buffer.is_empty());
+    assert_eq!(pretty(session.definitions[0].1.clone()), "3");
+  }
+
+  // :quit ends the session, and unknown commands do not
+  #[test]
+  fn commands() {
+    let (mut session, out) = session();
+    assert!(session.line(":nothing", &out));
+    assert!(!session.line(":quit", &out));
+  }
+}
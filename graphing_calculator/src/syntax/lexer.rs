@@ -9,14 +9,32 @@ pub enum TokenKind {
   Divide,
   Multiply,
   Power,
+  Root,
   Equals,
+  Less,
+  Greater,
+  LessEqual,
+  GreaterEqual,
   Comma,
   Semicolon,
+  Colon,
   Command,
+  Pipe,
+  Bang,
+  LeftBrace,
+  RightBrace,
+  // `..` between the ends of a range
+  Range,
 
   // Literals.
   Identifier,
   Number,
+  // superscript digits, such as the `²` of `x²`
+  Superscript,
+  // quoted text, such as the `"profit"` of `label="profit"`
+  Text,
+  // a hex colour, such as the `#ff0000` of `color=#ff0000`
+  Colour,
 
   // Extras.
   EOF,
@@ -36,12 +54,44 @@ pub struct Position {
   pub machine: usize,
 }
 
+// struct to hold an error found while lexing or parsing
+#[derive(PartialEq, Debug, Clone)]
+pub struct SyntaxError {
+  pub message: String,
+  pub position: Position,
+  // the input ended before the statement did, so more input could fix it
+  pub incomplete: bool,
+}
+
+impl std::fmt::Display for SyntaxError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "{}", self.message)
+  }
+}
+
+impl std::error::Error for SyntaxError {}
+
+// struct to hold input that parses, but perhaps not the way it was meant
+#[derive(PartialEq, Debug, Clone)]
+pub struct SyntaxWarning {
+  pub message: String,
+  pub position: Position,
+}
+
+impl std::fmt::Display for SyntaxWarning {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "{}", self.message)
+  }
+}
+
 // struct to hold a token
 #[derive(Clone)]
 pub struct Token {
   pub kind: TokenKind,
   pub value: String,
   pub position: Position,
+  // the bytes of the input the token was read from
+  pub span: std::ops::Range<usize>,
 }
 
 impl std::fmt::Debug for Token {
@@ -58,10 +108,12 @@ impl std::fmt::Debug for Token {
 impl Token {
   // instantiate a token
   pub fn new(kind: TokenKind, value: &dyn std::string::ToString, position: Position) -> Token {
+    let span = position.machine..position.machine;
     Token {
       kind,
       value: value.to_string(),
       position,
+      span,
     }
   }
 }
@@ -74,237 +126,622 @@ impl std::cmp::PartialEq for Token {
 }
 
 // struct to hold information for a lexer
+#[derive(Clone)]
 pub struct Lexer {
   input: String,
+  // each character of the input with the byte it starts at
+  chars: Vec<(usize, char)>,
+  // the index in chars of the current character
+  index: usize,
+  // the position of the current character
   position: Position,
   current_char: Option<char>,
+  // whether the characters are the options of a command, from an `@` to the end of its line or
+  // the next `;`
+  options: bool,
 }
 
 impl Lexer {
   // instantiate a lexer
   pub fn new(input: String) -> Lexer {
-    let mut lexer = Lexer {
-      input: input,
+    let chars: Vec<(usize, char)> = input.char_indices().collect();
+    let current_char = chars.first().map(|(_, c)| *c);
+    Lexer {
+      input,
+      chars,
+      index: 0,
       position: Position {
         human: HumanPosition { line: 1, column: 1 },
         machine: 0,
       },
-      current_char: Some(' '),
-    };
-    lexer.advance();
-    lexer
+      current_char,
+      options: false,
+    }
+  }
+
+  // the input a span of tokens was read from
+  pub fn source(&self, span: std::ops::Range<usize>) -> &str {
+    &self.input[span]
   }
 
   // method to call for lexing errors
-  fn error(&self, msg: String) -> ! {
-    panic!("{}", msg)
+  fn error(&self, msg: String) -> SyntaxError {
+    SyntaxError {
+      message: msg,
+      position: self.position.clone(),
+      incomplete: false,
+    }
   }
 
-  // advance the lexer
+  // advance the lexer by one character
   fn advance(&mut self) {
-    if self.position.machine > self.input.len() {
-      self.current_char = None; // Indicates end of input
-    } else {
-      self.current_char = self.input[self.position.machine..].chars().next();
+    match self.current_char {
+      Some('\n') => {
+        self.position.human.line += 1;
+        self.position.human.column = 1;
+      }
+      Some(_) => self.position.human.column += 1,
+      None => return,
     }
-    self.position.machine += 1;
-    self.position.human.column += 1;
+    self.index += 1;
+    self.current_char = self.chars.get(self.index).map(|(_, c)| *c);
+    self.position.machine = self
+      .chars
+      .get(self.index)
+      .map_or(self.input.len(), |(i, _)| *i);
   }
 
   // peek at the next character without advancing
   fn peek(&self) -> Option<char> {
-    let peek_pos = self.position.machine + 1;
-    if peek_pos > self.input.len() {
-      return None;
-    } else {
-      return self.input[peek_pos..].chars().next();
-    }
+    self.peek_nth(0)
   }
 
-  // dead code
-  // peek at the next token without advancing
-  // fn peek_token(&mut self) -> Token {
-  //   let position = self.position.clone();
-  //   let token = self.get_next_token();
-  //   self.position = position;
-  //   token
-  // }
+  // peek at the character n places after the next one
+  fn peek_nth(&self, n: usize) -> Option<char> {
+    self.chars.get(self.index + 1 + n).map(|(_, c)| *c)
+  }
+
+  // a token read from start up to the current character
+  fn token(&self, kind: TokenKind, value: &dyn std::string::ToString, start: Position) -> Token {
+    let mut token = Token::new(kind, value, start);
+    token.span = token.position.machine..self.position.machine;
+    token
+  }
 
   // advance until the token in not a whitespace character
   fn skip_whitespace(&mut self) {
-    while self.current_char.is_some() && self.current_char.unwrap().is_whitespace() {
-      if self.current_char.unwrap() == '\n' {
-        self.position.human.line += 1;
-        self.position.human.column = 1;
-      }
+    while let Some(c) = self.current_char.filter(|c| c.is_whitespace()) {
+      self.options &= c != '\n';
       self.advance();
     }
   }
 
   // skip until the end of the line (used for comments)
   fn skip_comment(&mut self) {
-    while self.current_char.is_some() && self.current_char.unwrap() != '\n' {
+    while self.current_char.is_some_and(|c| c != '\n') {
       self.advance();
     }
   }
 
-  // get a number and advance to the end of it
-  fn number(&mut self) -> String {
+  // an error for a literal that starts like a number but is not one
+  fn invalid_number(&self, start: &Position) -> SyntaxError {
+    // quote the rest of the literal too, not just as far as the mistake
+    let rest = &self.input[start.machine..];
+    let end = rest
+      .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+      .unwrap_or(rest.len());
+    SyntaxError {
+      message: format!(
+        "SyntaxError: Invalid number '{}' at position {}:{}",
+        &rest[..end],
+        start.human.line,
+        start.human.column
+      ),
+      position: start.clone(),
+      incomplete: false,
+    }
+  }
+
+  // read digits of the radix, allowing single underscores between them such as `1_000`
+  fn digits(&mut self, radix: u32, start: &Position) -> Result<String, SyntaxError> {
     let mut result = String::new();
-    while self.current_char.is_some() && self.current_char.unwrap().is_digit(10) {
-      result += &self.current_char.unwrap().to_string();
+    loop {
+      match self.current_char {
+        Some(c) if c.is_digit(radix) => result.push(c),
+        Some('_') => {
+          let between = !result.is_empty() && self.peek().is_some_and(|c| c.is_digit(radix));
+          if !between {
+            return Err(self.invalid_number(start));
+          }
+        }
+        _ => return Ok(result),
+      }
       self.advance();
     }
-    if self.current_char.is_some() && self.current_char.unwrap() == '.' {
-      result += ".";
+  }
+
+  // get a number literal, such as `12`, `1_000`, `.5`, `6.02e23` or `0x1F`
+  fn number(&mut self) -> Result<Token, SyntaxError> {
+    let start = self.position.clone();
+    // `0x` is only hexadecimal when a hex digit follows it, otherwise `0x` is zero times x
+    let hex = self.current_char == Some('0')
+      && matches!(self.peek(), Some('x' | 'X'))
+      && self.peek_nth(1).is_some_and(|c| c.is_ascii_hexdigit());
+    let value: f64 = if hex {
+      self.advance();
       self.advance();
-      while self.current_char.is_some() && self.current_char.unwrap().is_digit(10) {
-        result += &self.current_char.unwrap().to_string();
+      let digits = self.digits(16, &start)?;
+      u64::from_str_radix(&digits, 16).map_err(|_| self.invalid_number(&start))? as f64
+    } else {
+      let mut result = self.digits(10, &start)?;
+      // a `.` is only a decimal point when a digit follows it
+      if self.current_char == Some('.') && self.peek().is_some_and(|c| c.is_ascii_digit()) {
         self.advance();
+        result += ".";
+        result += &self.digits(10, &start)?;
+      }
+      // an `e` is only an exponent when a digit follows it, otherwise `2e` is two times e
+      if matches!(self.current_char, Some('e' | 'E')) {
+        let signed = matches!(self.peek(), Some('+' | '-'));
+        let digit = self
+          .peek_nth(signed as usize)
+          .is_some_and(|c| c.is_ascii_digit());
+        if digit {
+          result += "e";
+          self.advance();
+          if signed {
+            result.push(self.current_char.unwrap());
+            self.advance();
+          }
+          result += &self.digits(10, &start)?;
+        }
       }
+      result.parse().map_err(|_| self.invalid_number(&start))?
+    };
+    // `1.2.3` is a mistake rather than two numbers multiplied
+    if self.current_char == Some('.') && self.peek().is_some_and(|c| c.is_ascii_digit()) {
+      return Err(self.invalid_number(&start));
     }
-    result
+    if !value.is_finite() {
+      return Err(self.invalid_number(&start));
+    }
+    Ok(self.token(TokenKind::Number, &value, start))
   }
 
   fn identifier(&mut self) -> String {
+    // π is a name on its own, so `2πx` is `2 π x`
+    if self.current_char == Some('π') {
+      self.advance();
+      return "π".to_string();
+    }
     let mut result = String::new();
-    while self.current_char.unwrap().is_alphabetic() {
-      result += &self.current_char.unwrap().to_string();
+    while let Some(c) = self.current_char.filter(|c| c.is_alphabetic() && *c != 'π') {
+      result.push(c);
       self.advance();
     }
     result
   }
 
+  // get a `#` and the hex digits after it, either three or six of them
+  fn colour(&mut self) -> Result<Token, SyntaxError> {
+    let start = self.position.clone();
+    self.advance();
+    let mut result = "#".to_string();
+    while let Some(c) = self.current_char.filter(|c| c.is_alphanumeric()) {
+      result.push(c);
+      self.advance();
+    }
+    let digits = &result[1..];
+    if ![3, 6].contains(&digits.len()) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+      return Err(SyntaxError {
+        message: format!(
+          "SyntaxError: Invalid colour '{}' at position {}:{}",
+          result, start.human.line, start.human.column
+        ),
+        position: start,
+        incomplete: false,
+      });
+    }
+    Ok(self.token(TokenKind::Colour, &result, start))
+  }
+
+  // get the text between a pair of `"`, which may not run past the end of the line
+  fn text(&mut self) -> Result<Token, SyntaxError> {
+    let start = self.position.clone();
+    self.advance();
+    let mut result = String::new();
+    while let Some(c) = self.current_char.filter(|c| !['"', '\n'].contains(c)) {
+      result.push(c);
+      self.advance();
+    }
+    if self.current_char != Some('"') {
+      return Err(SyntaxError {
+        message: format!(
+          "SyntaxError: Unterminated text at position {}:{}",
+          start.human.line, start.human.column
+        ),
+        position: start,
+        incomplete: false,
+      });
+    }
+    self.advance();
+    Ok(self.token(TokenKind::Text, &result, start))
+  }
+
+  // get superscript digits as ascii, with a leading minus for `⁻`
+  fn superscript(&mut self) -> Result<String, SyntaxError> {
+    let mut result = String::new();
+    if self.current_char == Some('⁻') {
+      result.push('-');
+      self.advance();
+    }
+    while let Some(digit) = self.current_char.and_then(superscript_digit) {
+      result.push(digit);
+      self.advance();
+    }
+    if result.ends_with('-') {
+      return Err(self.error(format!(
+        "SyntaxError: Expected a superscript digit at position {}:{}",
+        self.position.human.line, self.position.human.column
+      )));
+    }
+    Ok(result)
+  }
+
   // get the next token (the main method)
-  pub fn get_next_token(&mut self) -> Token {
-    while self.current_char.is_some() {
+  pub fn get_next_token(&mut self) -> Result<Token, SyntaxError> {
+    while let Some(c) = self.current_char {
+      let start = self.position.clone();
+
+      // get a colour, where a `#` straight after the `=` of a command's option is part of
+      // `color=#ff0000` rather than a comment
+      if c == '#' && self.options && self.index > 0 && self.chars[self.index - 1].1 == '=' {
+        return self.colour();
+      }
+
       // skip comments
-      if self.current_char.unwrap() == '#' {
+      if c == '#' {
         self.skip_comment();
         continue;
       }
 
       // skip whitespace
-      if self.current_char.unwrap().is_whitespace() {
+      if c.is_whitespace() {
         self.skip_whitespace();
         continue;
       }
 
-      // get a number
-      if self.current_char.unwrap().is_digit(10) {
-        let number = self.number();
-        return Token::new(
-          TokenKind::Number,
-          &number,
-          Position {
-            human: HumanPosition {
-              line: self.position.human.line,
-              column: self.position.human.column - number.len(),
-            },
-            machine: self.position.machine,
-          },
-        );
+      // get quoted text
+      if c == '"' {
+        return self.text();
       }
 
-      // get a command
-      if self.current_char.unwrap() == '@' {
-        self.advance();
-        return Token::new(TokenKind::Command, &'@', self.position.clone());
+      // get a number
+      if c.is_ascii_digit() || (c == '.' && self.peek().is_some_and(|c| c.is_ascii_digit())) {
+        return self.number();
       }
 
       // get an identifier
-      if self.current_char.unwrap().is_alphabetic() {
+      if c.is_alphabetic() {
         let identifier = self.identifier();
-        return Token::new(
-          TokenKind::Identifier,
-          &identifier,
-          Position {
-            human: HumanPosition {
-              line: self.position.human.line,
-              column: self.position.human.column - identifier.len(),
-            },
-            machine: self.position.machine,
-          },
-        );
+        return Ok(self.token(TokenKind::Identifier, &identifier, start));
       }
 
-      // get an assignment
-      if self.current_char.unwrap() == '=' {
-        self.advance();
-        return Token::new(TokenKind::Equals, &'=', self.position.clone());
+      // get a power written in superscript
+      if c == '⁻' || superscript_digit(c).is_some() {
+        let superscript = self.superscript()?;
+        return Ok(self.token(TokenKind::Superscript, &superscript, start));
       }
 
-      // get a semicolon
-      if self.current_char.unwrap() == ';' {
+      // get a plus-minus written as two characters
+      if c == '+' && self.peek() == Some('-') {
         self.advance();
-        return Token::new(TokenKind::Semicolon, &';', self.position.clone());
-      }
-
-      // get a comma
-      if self.current_char.unwrap() == ',' {
         self.advance();
-        return Token::new(TokenKind::Comma, &',', self.position.clone());
+        return Ok(self.token(TokenKind::AddSubtract, &"+-", start));
       }
 
-      // get a plus-minus
-      if self.current_char.unwrap() == '+' && self.peek().unwrap() == '-' {
+      // get the `..` of a range such as `-5..5`, where a `.` before a digit is a decimal point
+      if c == '.' && self.peek() == Some('.') {
         self.advance();
-        let pos = self.position.clone();
         self.advance();
-        return Token::new(TokenKind::AddSubtract, &"+-", pos);
+        return Ok(self.token(TokenKind::Range, &"..", start));
       }
 
-      // get a plus
-      if self.current_char.unwrap() == '+' {
+      // get a comparison, `≤` and `≥` are the same as `<=` and `>=`
+      if ['<', '>'].contains(&c) && self.peek() == Some('=') {
         self.advance();
-        return Token::new(TokenKind::Add, &'+', self.position.clone());
-      }
-
-      // get a minus
-      if self.current_char.unwrap() == '-' {
         self.advance();
-        return Token::new(TokenKind::Subtract, &'-', self.position.clone());
+        let kind = match c {
+          '<' => TokenKind::LessEqual,
+          _ => TokenKind::GreaterEqual,
+        };
+        return Ok(self.token(kind, &format!("{}=", c), start));
       }
 
-      // get a multiply
-      if self.current_char.unwrap() == '*' {
+      // get a single character token, typographic forms read the same as ascii
+      let single = match c {
+        '(' => Some((TokenKind::LeftParen, "(")),
+        ')' => Some((TokenKind::RightParen, ")")),
+        '+' => Some((TokenKind::Add, "+")),
+        '-' | '−' => Some((TokenKind::Subtract, "-")),
+        '±' => Some((TokenKind::AddSubtract, "±")),
+        '*' | '×' => Some((TokenKind::Multiply, "*")),
+        '/' | '÷' => Some((TokenKind::Divide, "/")),
+        '^' => Some((TokenKind::Power, "^")),
+        '√' => Some((TokenKind::Root, "√")),
+        '=' => Some((TokenKind::Equals, "=")),
+        '<' => Some((TokenKind::Less, "<")),
+        '>' => Some((TokenKind::Greater, ">")),
+        '≤' => Some((TokenKind::LessEqual, "<=")),
+        '≥' => Some((TokenKind::GreaterEqual, ">=")),
+        ',' => Some((TokenKind::Comma, ",")),
+        ';' => Some((TokenKind::Semicolon, ";")),
+        ':' => Some((TokenKind::Colon, ":")),
+        '|' => Some((TokenKind::Pipe, "|")),
+        '!' => Some((TokenKind::Bang, "!")),
+        '{' => Some((TokenKind::LeftBrace, "{")),
+        '}' => Some((TokenKind::RightBrace, "}")),
+        '@' => Some((TokenKind::Command, "@")),
+        _ => None,
+      };
+      if let Some((kind, value)) = single {
+        match kind {
+          TokenKind::Command => self.options = true,
+          TokenKind::Semicolon => self.options = false,
+          _ => {}
+        }
         self.advance();
-        return Token::new(TokenKind::Multiply, &'*', self.position.clone());
+        return Ok(self.token(kind, &value, start));
       }
 
-      // get a power
-      if self.current_char.unwrap() == '^' {
-        self.advance();
-        return Token::new(TokenKind::Power, &'^', self.position.clone());
-      }
+      // if none of the above, error
+      return Err(self.error(format!(
+        "SyntaxError: Unexpected '{}' at position {}:{}",
+        c, self.position.human.line, self.position.human.column
+      )));
+    }
 
-      // get a divide
-      if self.current_char.unwrap() == '/' {
-        self.advance();
-        return Token::new(TokenKind::Divide, &'/', self.position.clone());
-      }
+    Ok(self.token(TokenKind::EOF, &"EOF", self.position.clone()))
+  }
+}
 
-      // get a left parenthesis
-      if self.current_char.unwrap() == '(' {
-        self.advance();
-        return Token::new(TokenKind::LeftParen, &'(', self.position.clone());
+// the ascii digit a superscript digit stands for
+fn superscript_digit(c: char) -> Option<char> {
+  "⁰¹²³⁴⁵⁶⁷⁸⁹"
+    .chars()
+    .position(|s| s == c)
+    .and_then(|d| char::from_digit(d as u32, 10))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use proptest::prelude::*;
+
+  fn numbers(input: &str) -> Result<Vec<String>, SyntaxError> {
+    let mut lexer = Lexer::new(input.to_string());
+    let mut out = Vec::new();
+    loop {
+      let token = lexer.get_next_token()?;
+      match token.kind {
+        TokenKind::EOF => return Ok(out),
+        _ => out.push(token.value),
       }
+    }
+  }
 
-      // get a right parenthesis
-      if self.current_char.unwrap() == ')' {
-        self.advance();
-        return Token::new(TokenKind::RightParen, &')', self.position.clone());
+  // every form of literal is read as the number it stands for
+  #[test]
+  fn literals() {
+    assert_eq!(
+      numbers("12 1_000 .5 1.25 6.02e23 1E-3 2.5e+2 0x1F 0XfF_fF").unwrap(),
+      vec![
+        "12",
+        "1000",
+        "0.5",
+        "1.25",
+        "602000000000000000000000",
+        "0.001",
+        "250",
+        "31",
+        "65535"
+      ]
+    );
+  }
+
+  // an `e` without digits after it is the constant e, and `.` needs a digit to be a decimal point
+  #[test]
+  fn not_exponents() {
+    assert_eq!(numbers("2e").unwrap(), vec!["2", "e"]);
+    assert_eq!(numbers("2e+x").unwrap(), vec!["2", "e", "+", "x"]);
+    assert!(numbers("2.").is_err());
+  }
+
+  // `0x` needs a hex digit after it to be hexadecimal, otherwise it is zero and x
+  #[test]
+  fn not_hex() {
+    assert_eq!(numbers("0x").unwrap(), vec!["0", "x"]);
+    assert_eq!(numbers("0x + 1").unwrap(), vec!["0", "x", "+", "1"]);
+    assert_eq!(numbers("0xA").unwrap(), vec!["10"]);
+  }
+
+  // malformed literals are reported where they start
+  #[test]
+  fn invalid() {
+    for (input, column) in [
+      ("1__0", 1),
+      ("y = 1_", 5),
+      ("0x1_", 1),
+      ("0xF__F", 1),
+      ("1.2.3", 1),
+      ("1e999", 1),
+    ] {
+      let error = numbers(input).unwrap_err();
+      assert!(error.message.contains("Invalid number"), "{}", input);
+      assert!(
+        input.ends_with(error.message.split('\'').nth(1).unwrap()),
+        "{}",
+        input
+      );
+      assert_eq!(error.position.human.column, column, "{}", input);
+    }
+  }
+
+  // the span covers exactly the literal
+  #[test]
+  fn span() {
+    let mut lexer = Lexer::new("y = 1_000.5e1;".to_string());
+    for _ in 0..2 {
+      lexer.get_next_token().unwrap();
+    }
+    assert_eq!(lexer.get_next_token().unwrap().span, 4..13);
+  }
+
+  fn tokens(input: &str) -> Vec<Token> {
+    let mut lexer = Lexer::new(input.to_string());
+    let mut out = Vec::new();
+    loop {
+      let token = lexer.get_next_token().unwrap();
+      if token.kind == TokenKind::EOF {
+        return out;
       }
+      out.push(token);
+    }
+  }
 
-      // if none of the above, panic
-      self.error(format!(
-        "SyntaxError: Unexpected '{}' at position {}:{}",
-        self.current_char.unwrap_or(' '),
-        self.position.human.line,
-        self.position.human.column - 1
-      ));
+  // a `#` after the `=` of an option is a colour and anywhere else starts a comment, and text is
+  // read between quotes
+  #[test]
+  fn colours_and_text() {
+    let kinds = |input: &str| -> Vec<(TokenKind, String)> {
+      tokens(input)
+        .into_iter()
+        .map(|t| (t.kind, t.value))
+        .collect()
+    };
+    assert_eq!(
+      kinds("@draw color=#ff0000 # red\nlabel=\"net profit\""),
+      vec![
+        (TokenKind::Command, "@".to_string()),
+        (TokenKind::Identifier, "draw".to_string()),
+        (TokenKind::Identifier, "color".to_string()),
+        (TokenKind::Equals, "=".to_string()),
+        (TokenKind::Colour, "#ff0000".to_string()),
+        (TokenKind::Identifier, "label".to_string()),
+        (TokenKind::Equals, "=".to_string()),
+        (TokenKind::Text, "net profit".to_string()),
+      ]
+    );
+    assert_eq!(kinds("y = x #1"), kinds("y = x"));
+    // outside a command's options a `#` straight after `=` is still a comment
+    for input in ["y =#note\nx", "@draw; y =#note\nx", "@draw\ny=#note\nx"] {
+      let found = kinds(input);
+      assert!(
+        found.iter().all(|(kind, _)| *kind != TokenKind::Colour),
+        "{}",
+        input
+      );
+      assert_eq!(
+        found.last(),
+        Some(&(TokenKind::Identifier, "x".to_string())),
+        "{}",
+        input
+      );
     }
+    assert_eq!(
+      kinds("1..2.5"),
+      vec![
+        (TokenKind::Number, "1".to_string()),
+        (TokenKind::Range, "..".to_string()),
+        (TokenKind::Number, "2.5".to_string()),
+      ]
+    );
+    for input in ["@draw color=#ff00", "@draw color=#red", "label=\"profit\nx"] {
+      assert!(numbers(input).is_err(), "{}", input);
+    }
+  }
+
+  // multibyte characters in comments and expressions neither panic nor shift later positions
+  #[test]
+  fn utf8_positions() {
+    let input = "# ünïcödé ✓ 日本語\ny = π² − √x; # ½\n  z ≤ 2";
+    let found: Vec<(String, usize, usize)> = tokens(input)
+      .iter()
+      .map(|t| {
+        (
+          t.value.clone(),
+          t.position.human.line,
+          t.position.human.column,
+        )
+      })
+      .collect();
+    let expected = [
+      ("y", 2, 1),
+      ("=", 2, 3),
+      ("π", 2, 5),
+      ("2", 2, 6),
+      ("-", 2, 8),
+      ("√", 2, 10),
+      ("x", 2, 11),
+      (";", 2, 12),
+      ("z", 3, 3),
+      ("<=", 3, 5),
+      ("2", 3, 7),
+    ];
+    assert_eq!(
+      found,
+      expected
+        .iter()
+        .map(|(v, l, c)| (v.to_string(), *l, *c))
+        .collect::<Vec<_>>()
+    );
+  }
+
+  // each span slices exactly the text of its token out of the input
+  #[test]
+  fn utf8_spans() {
+    let input = "# ✓\nαβ × 1_0 ÷ 2² +- 3 ≥ .5 # ü";
+    let texts: Vec<&str> = tokens(input)
+      .iter()
+      .map(|t| &input[t.span.clone()])
+      .collect();
+    assert_eq!(
+      texts,
+      vec!["αβ", "×", "1_0", "÷", "2", "²", "+-", "3", "≥", ".5"]
+    );
+  }
 
-    Token::new(TokenKind::EOF, &"EOF", self.position.clone())
+  // an unexpected character is reported where it is, counting characters rather than bytes
+  #[test]
+  fn unexpected_position() {
+    let mut lexer = Lexer::new("é + $".to_string());
+    lexer.get_next_token().unwrap();
+    lexer.get_next_token().unwrap();
+    let error = lexer.get_next_token().unwrap_err();
+    assert_eq!(error.position.human.column, 5);
+    assert_eq!(error.position.machine, 5);
+  }
+
+  proptest! {
+    // any input is read to the end in order, or stops at an error inside it
+    #[test]
+    fn never_panics(input in "(?s).{0,48}") {
+      let mut lexer = Lexer::new(input.clone());
+      let mut end = 0;
+      loop {
+        match lexer.get_next_token() {
+          Ok(token) if token.kind == TokenKind::EOF => break,
+          Ok(token) => {
+            prop_assert!(end <= token.span.start && token.span.end <= input.len());
+            end = token.span.end;
+          }
+          Err(error) => {
+            prop_assert!(error.message.starts_with("SyntaxError"));
+            prop_assert!(error.position.machine <= input.len());
+            break;
+          }
+        }
+      }
+    }
   }
 }
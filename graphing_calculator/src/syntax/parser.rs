@@ -1,6 +1,40 @@
-use super::lexer::{Lexer, Token, TokenKind};
+use super::lexer::{HumanPosition, Lexer, Position, SyntaxError, SyntaxWarning, Token, TokenKind};
+use crate::functions::constant;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-#[derive(Clone, PartialEq)]
+// how tightly each operator binds, loosest first
+//   `=`                     identity, `a = b = c` is one chain
+//   `<` `>` `<=` `>=`       comparison, only in the condition of a piecewise case
+//   `+` `-` `±`             sum, left to right
+//   `*` `/` `mod`           product, left to right so `a/b*c` is `(a/b)c`
+//   `2x` `2(x + 1)`         juxtaposition, tighter than `*` and `/` so `1/2x` is `1/(2x)`
+//   `-` `+` `±` `√`         unary, looser than a power so `-x^2` is `-(x^2)`
+//   `!`                     factorial, `n!^2` is `(n!)^2`
+//   `^` superscripts        power, right to left so `a^b^c` is `a^(b^c)` and `2^-x` is `2^(-x)`
+
+// how many factors can be read inside one another, such as `((x))` or `--x`
+const MAX_DEPTH: usize = 128;
+
+// names that are read as a call when followed by brackets, rather than multiplying them
+pub const FUNCTIONS: [&str; 14] = [
+  "sqrt",
+  "sin",
+  "cos",
+  "tan",
+  "asin",
+  "acos",
+  "atan",
+  "ln",
+  "abs",
+  "floor",
+  "ceil",
+  "min",
+  "max",
+  "factorial",
+];
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub enum AST {
   Expr(Expr),
   Term(Term),
@@ -10,6 +44,9 @@ pub enum AST {
   Number(f64),
   Call(Call),
   Identity(Identity),
+  Compare(Compare),
+  Piecewise(Piecewise),
+  Points(Points),
   Statement(Statement),
 }
 
@@ -24,12 +61,15 @@ impl std::fmt::Debug for AST {
       AST::Number(number) => write!(f, "{:#?}", number),
       AST::Call(call) => write!(f, "{:#?}", call),
       AST::Identity(identity) => write!(f, "{:#?}", identity),
+      AST::Compare(compare) => write!(f, "{:#?}", compare),
+      AST::Piecewise(piecewise) => write!(f, "{:#?}", piecewise),
+      AST::Points(points) => write!(f, "{:#?}", points),
       AST::Statement(statement) => write!(f, "{:#?}", statement),
     }
   }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Sign {
   Add,
   Sub,
@@ -37,257 +77,1093 @@ pub enum Sign {
   Mul,
   Div,
   Pow,
+  Mod,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Relation {
+  Less,
+  Greater,
+  LessEqual,
+  GreaterEqual,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Expr {
   pub sign: Sign,
   pub expr: Vec<Box<AST>>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Term {
   pub sign: Sign,
   pub term: Vec<Box<AST>>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Index {
   pub sign: Sign,
   pub index: (Box<AST>, Box<AST>),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Unary {
   pub sign: Sign,
   pub unary: Box<AST>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Call {
   pub name: String,
   pub call: Vec<Box<AST>>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Identity {
   pub identity: Vec<Box<AST>>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Compare {
+  pub relation: Relation,
+  pub compare: (Box<AST>, Box<AST>),
+}
+
+// the value of the first case whose condition holds, or otherwise when none do
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Piecewise {
+  pub piecewise: Vec<(Box<AST>, Box<AST>)>,
+  pub otherwise: Option<Box<AST>>,
+}
+
+// measured `(x, y)` pairs, plotted as they are rather than as a curve
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Points {
+  pub points: Vec<(Box<AST>, Box<AST>)>,
+}
+
+// a value given to a command, by its place or after `name=`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Argument {
+  // a factor, such as the `-1` of `@tangent -1` or the `linear` of `@fit linear`
+  Expr(Box<AST>),
+  // quoted text or a colour, such as `"profit"` or `#ff0000`
+  Text(String),
+  // the ends of `from..to`, such as `-5..5`
+  Range(Box<AST>, Box<AST>),
+}
+
+impl Argument {
+  // a lone word, number or text, for settings such as `style=dashed` or `width=2`
+  pub fn word(&self) -> Option<String> {
+    match self {
+      Argument::Text(text) => Some(text.clone()),
+      Argument::Expr(e) => match e.as_ref() {
+        AST::Variable(v) => Some(v.clone()),
+        AST::Number(n) => Some(n.to_string()),
+        _ => None,
+      },
+      Argument::Range(..) => None,
+    }
+  }
+
+  // the argument with f applied to each expression in it
+  pub fn map(self, f: &mut impl FnMut(Box<AST>) -> Box<AST>) -> Argument {
+    match self {
+      Argument::Expr(e) => Argument::Expr(f(e)),
+      Argument::Range(from, to) => Argument::Range(f(from), f(to)),
+      text => text,
+    }
+  }
+}
+
+// one `@name` after a statement with the arguments and options that follow it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Command {
+  pub name: String,
+  pub arguments: Vec<Argument>,
+  // the `name=value` settings, such as the `style=dashed` of `@draw style=dashed`
+  pub options: BTreeMap<String, Argument>,
+}
+
+impl Command {
+  // the command with f applied to each expression in its arguments and options
+  pub fn map(self, f: &mut impl FnMut(Box<AST>) -> Box<AST>) -> Command {
+    Command {
+      name: self.name,
+      arguments: self.arguments.into_iter().map(|a| a.map(f)).collect(),
+      options: self
+        .options
+        .into_iter()
+        .map(|(name, value)| (name, value.map(f)))
+        .collect(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Statement {
   pub statement: Box<AST>,
-  pub command: Option<String>,
+  // every command after the statement, in the order they were written
+  pub commands: Vec<Command>,
+}
+
+impl Statement {
+  // whether any of the commands has this name
+  pub fn has(&self, name: &str) -> bool {
+    self.commands.iter().any(|c| c.name == name)
+  }
 }
 
 pub struct Parser {
   lexer: Lexer,
   current_token: Token,
+  // the token after the current one, once something has looked ahead to it
+  peeked: Option<Result<Token, SyntaxError>>,
+  // how many `|` are open, a `|` inside one closes it rather than starting another
+  abs: usize,
+  // how many factors are being read inside one another
+  depth: usize,
+  // the end of the last token eaten
+  end: usize,
+  warnings: Vec<SyntaxWarning>,
 }
 
 impl Parser {
   pub fn new(input: String) -> Parser {
-    let mut lexer = Lexer::new(input);
-    let current_token = lexer.get_next_token();
+    let lexer = Lexer::new(input);
+    // the first token is read when parsing starts so lexing errors can be returned
+    let current_token = Token::new(
+      TokenKind::EOF,
+      &"",
+      Position {
+        human: HumanPosition { line: 1, column: 1 },
+        machine: 0,
+      },
+    );
     Parser {
       lexer,
       current_token,
+      peeked: None,
+      abs: 0,
+      depth: 0,
+      end: 0,
+      warnings: Vec::new(),
+    }
+  }
+
+  // the token after the current one, taking the one already peeked at if there is one
+  fn next_token(&mut self) -> Result<Token, SyntaxError> {
+    match self.peeked.take() {
+      Some(token) => token,
+      None => self.lexer.get_next_token(),
     }
   }
 
-  // method to call for lexing errors
-  fn error(&self, msg: String) -> ! {
-    panic!("{}", msg)
+  // look at the token after the current one without eating the current one, where an error is kept
+  // to be returned when the token is eaten
+  fn peek(&mut self) -> Option<&Token> {
+    let lexer = &mut self.lexer;
+    self
+      .peeked
+      .get_or_insert_with(|| lexer.get_next_token())
+      .as_ref()
+      .ok()
   }
 
-  fn eat(&mut self, token_type: TokenKind) {
+  // method to call for parsing errors
+  fn error(&self, msg: String) -> SyntaxError {
+    SyntaxError {
+      message: msg,
+      position: self.current_token.position.clone(),
+      incomplete: self.current_token.kind == TokenKind::EOF,
+    }
+  }
+
+  // input that parsed but may not mean what was intended, such as `a/bc`
+  pub fn warnings(&self) -> &[SyntaxWarning] {
+    &self.warnings
+  }
+
+  fn eat(&mut self, token_type: TokenKind) -> Result<(), SyntaxError> {
     if self.current_token.kind == token_type {
-      self.current_token = self.lexer.get_next_token();
+      self.end = self.current_token.span.end;
+      self.current_token = self.next_token()?;
+      Ok(())
     } else {
-      self.error(format!(
+      Err(self.error(format!(
         "SyntaxError: Unexpected {:?}: '{}' at position {}:{} expecting {:?}",
         self.current_token.kind.clone(),
         self.current_token.value.clone(),
         self.current_token.position.human.line.clone(),
         self.current_token.position.human.column.clone(),
         token_type,
-      ));
+      )))
     }
   }
 
-  fn factor(&mut self) -> Box<AST> {
+  // each bracket, sign and power reads one factor inside another, so limit how deep that
+  // goes rather than run out of stack
+  fn factor(&mut self) -> Result<Box<AST>, SyntaxError> {
+    if self.depth == MAX_DEPTH {
+      return Err(self.error(format!(
+        "SyntaxError: Too deeply nested at position {}:{}",
+        self.current_token.position.human.line, self.current_token.position.human.column,
+      )));
+    }
+    self.depth += 1;
+    let node = self.operand();
+    self.depth -= 1;
+    node
+  }
+
+  fn operand(&mut self) -> Result<Box<AST>, SyntaxError> {
     let token = self.current_token.clone();
     let mut node: Box<AST>;
     if token.kind == TokenKind::Add {
-      self.eat(TokenKind::Add);
+      self.eat(TokenKind::Add)?;
       node = Box::new(AST::Unary(Unary {
         sign: Sign::Add,
-        unary: self.factor(),
+        unary: self.factor()?,
       }));
     } else if token.kind == TokenKind::Subtract {
-      self.eat(TokenKind::Subtract);
+      self.eat(TokenKind::Subtract)?;
       node = Box::new(AST::Unary(Unary {
         sign: Sign::Sub,
-        unary: self.factor(),
+        unary: self.factor()?,
       }));
     } else if token.kind == TokenKind::AddSubtract {
-      self.eat(TokenKind::AddSubtract);
+      self.eat(TokenKind::AddSubtract)?;
       node = Box::new(AST::Unary(Unary {
         sign: Sign::AddSub,
-        unary: self.factor(),
+        unary: self.factor()?,
+      }));
+    } else if token.kind == TokenKind::Root {
+      // `√x` is `sqrt(x)`, taking a single factor like a unary sign
+      self.eat(TokenKind::Root)?;
+      node = Box::new(AST::Call(Call {
+        name: "sqrt".to_string(),
+        call: vec![self.factor()?],
       }));
     } else if token.kind == TokenKind::Number {
-      self.eat(TokenKind::Number);
-      node = Box::new(AST::Number(token.value.parse().unwrap()));
+      self.eat(TokenKind::Number)?;
+      // the lexer has already checked the literal
+      node = Box::new(AST::Number(token.value.parse().map_err(|_| {
+        self.error(format!("SyntaxError: Invalid number '{}'", token.value))
+      })?));
     } else if token.kind == TokenKind::Identifier {
-      self.eat(TokenKind::Identifier);
-      node = Box::new(AST::Variable(token.value));
+      self.eat(TokenKind::Identifier)?;
+      if FUNCTIONS.contains(&token.value.as_str())
+        && self.current_token.kind == TokenKind::LeftParen
+      {
+        node = self.call(token.value)?;
+      } else {
+        node = Box::new(AST::Variable(token.value));
+      }
     } else if token.kind == TokenKind::LeftParen {
-      self.eat(TokenKind::LeftParen);
-      node = self.expr();
-      self.eat(TokenKind::RightParen);
+      self.eat(TokenKind::LeftParen)?;
+      node = self.nested(|p| p.expr())?;
+      self.eat(TokenKind::RightParen)?;
+    } else if token.kind == TokenKind::Pipe {
+      // `|x|` is `abs(x)`
+      self.eat(TokenKind::Pipe)?;
+      self.abs += 1;
+      let inner = self.expr();
+      self.abs -= 1;
+      node = Box::new(AST::Call(Call {
+        name: "abs".to_string(),
+        call: vec![inner?],
+      }));
+      self.eat(TokenKind::Pipe)?;
+    } else if token.kind == TokenKind::LeftBrace {
+      node = self.nested(|p| p.piecewise())?;
     } else {
-      self.error(format!(
+      return Err(self.error(format!(
         "SyntaxError: Unexpected {:?}: '{}' at position {}:{}",
         self.current_token.kind.clone(),
         self.current_token.value.clone(),
         self.current_token.position.human.line.clone(),
         self.current_token.position.human.column.clone(),
-      ));
+      )));
+    }
+    // `n!` binds tighter than a power, so `n!^2` is `(n!)^2`
+    while self.current_token.kind == TokenKind::Bang {
+      self.eat(TokenKind::Bang)?;
+      node = Box::new(AST::Call(Call {
+        name: "factorial".to_string(),
+        call: vec![node],
+      }));
     }
     if self.current_token.kind == TokenKind::Power {
-      self.eat(TokenKind::Power);
+      self.eat(TokenKind::Power)?;
+      node = Box::new(AST::Index(Index {
+        sign: Sign::Pow,
+        index: (node, self.factor()?),
+      }));
+    } else if self.current_token.kind == TokenKind::Superscript {
+      // `x⁻²` is read as `x^-2`
+      let value = self.current_token.value.clone();
+      let number = |digits: &str| {
+        digits
+          .parse()
+          .map(AST::Number)
+          .map_err(|_| self.error(format!("SyntaxError: Invalid superscript '{}'", value)))
+      };
+      let exponent = match value.strip_prefix('-') {
+        Some(digits) => AST::Unary(Unary {
+          sign: Sign::Sub,
+          unary: Box::new(number(digits)?),
+        }),
+        None => number(&value)?,
+      };
+      self.eat(TokenKind::Superscript)?;
       node = Box::new(AST::Index(Index {
         sign: Sign::Pow,
-        index: (node, self.factor()),
+        index: (node, Box::new(exponent)),
       }));
     }
 
-    node
+    Ok(node)
+  }
+
+  // parse inside brackets, where a `|` starts a new `|x|` even within another
+  fn nested<T>(
+    &mut self,
+    parse: impl FnOnce(&mut Parser) -> Result<T, SyntaxError>,
+  ) -> Result<T, SyntaxError> {
+    let abs = std::mem::take(&mut self.abs);
+    let result = parse(self);
+    self.abs = abs;
+    result
   }
 
-  fn term(&mut self) -> Box<AST> {
-    let mut node = self.factor();
+  // the bracketed arguments of a function
+  fn call(&mut self, name: String) -> Result<Box<AST>, SyntaxError> {
+    self.eat(TokenKind::LeftParen)?;
+    let call = self.nested(|p| {
+      let mut call = vec![p.expr()?];
+      while p.current_token.kind == TokenKind::Comma {
+        p.eat(TokenKind::Comma)?;
+        call.push(p.expr()?);
+      }
+      Ok(call)
+    })?;
+    self.eat(TokenKind::RightParen)?;
+    Ok(Box::new(AST::Call(Call { name, call })))
+  }
+
+  // an expression, or a comparison of two when followed by `<`, `>`, `<=` or `>=`
+  fn comparison(&mut self) -> Result<Box<AST>, SyntaxError> {
+    let left = self.expr()?;
+    let relation = match self.current_token.kind {
+      TokenKind::Less => Relation::Less,
+      TokenKind::Greater => Relation::Greater,
+      TokenKind::LessEqual => Relation::LessEqual,
+      TokenKind::GreaterEqual => Relation::GreaterEqual,
+      _ => return Ok(left),
+    };
+    self.eat(self.current_token.kind)?;
+    Ok(Box::new(AST::Compare(Compare {
+      relation,
+      compare: (left, self.expr()?),
+    })))
+  }
+
+  // `{x < 0: -x; x}`, cases separated by `;` with an optional last case for everything else
+  fn piecewise(&mut self) -> Result<Box<AST>, SyntaxError> {
+    self.eat(TokenKind::LeftBrace)?;
+    let mut piecewise = Piecewise {
+      piecewise: Vec::new(),
+      otherwise: None,
+    };
+    loop {
+      let condition = self.comparison()?;
+      if self.current_token.kind == TokenKind::Colon {
+        self.eat(TokenKind::Colon)?;
+        piecewise.piecewise.push((condition, self.expr()?));
+      } else if matches!(*condition, AST::Compare(_)) || piecewise.piecewise.is_empty() {
+        // the case for everything else has no condition, and cannot be the only one
+        self.eat(TokenKind::Colon)?;
+      } else {
+        piecewise.otherwise = Some(condition);
+        break;
+      }
+      if self.current_token.kind != TokenKind::Semicolon {
+        break;
+      }
+      self.eat(TokenKind::Semicolon)?;
+    }
+    self.eat(TokenKind::RightBrace)?;
+    Ok(Box::new(AST::Piecewise(piecewise)))
+  }
+
+  fn is_mod(&self) -> bool {
+    self.current_token.kind == TokenKind::Identifier && self.current_token.value == "mod"
+  }
 
-    while [
-      TokenKind::Multiply,
-      TokenKind::Divide,
+  // factors next to each other, such as `2x(x + 1)`, which are multiplied
+  fn product(&mut self) -> Result<Vec<Box<AST>>, SyntaxError> {
+    let mut product = vec![self.factor()?];
+    while ([
       TokenKind::LeftParen,
       TokenKind::Identifier,
       TokenKind::Number,
+      TokenKind::Root,
+      TokenKind::LeftBrace,
     ]
     .contains(&self.current_token.kind)
+      && !self.is_mod())
+      || (self.current_token.kind == TokenKind::Pipe && self.abs == 0)
     {
-      let mut token = self.current_token.clone();
-      if token.kind == TokenKind::Multiply {
-        self.eat(TokenKind::Multiply);
-      } else if token.kind == TokenKind::Divide {
-        self.eat(TokenKind::Divide);
-      } else if [
-        TokenKind::LeftParen,
-        TokenKind::Identifier,
-        TokenKind::Number,
-      ]
-      .contains(&self.current_token.kind)
+      product.push(self.factor()?);
+    }
+    Ok(product)
+  }
+
+  // a name of several letters that is not a constant, such as the `bc` of `a/bc`, or the same name
+  // raised to a power
+  fn several_letters(ast: &AST) -> bool {
+    match ast {
+      AST::Variable(name) => name.chars().count() > 1 && constant(name).is_none(),
+      AST::Index(index) => Parser::several_letters(&index.index.0),
+      _ => false,
+    }
+  }
+
+  fn multiply(mut product: Vec<Box<AST>>) -> Box<AST> {
+    if product.len() == 1 {
+      product.remove(0)
+    } else {
+      Box::new(AST::Term(Term {
+        sign: Sign::Mul,
+        term: product,
+      }))
+    }
+  }
+
+  fn term(&mut self) -> Result<Box<AST>, SyntaxError> {
+    let mut node = Parser::multiply(self.product()?);
+
+    loop {
+      let operator = self.current_token.clone();
+      let sign = match operator.kind {
+        TokenKind::Multiply => Sign::Mul,
+        TokenKind::Divide => Sign::Div,
+        _ if self.is_mod() => Sign::Mod,
+        _ => break,
+      };
+      self.eat(operator.kind)?;
+      let first = self.current_token.clone();
+      let product = self.product()?;
+      if sign != Sign::Mul && product.len() > 1 {
+        // `a/bc` divides by the whole of `bc`, which is easily misread
+        let symbol = self.lexer.source(operator.span.clone());
+        let divisor = self.lexer.source(first.span.start..self.end);
+        let message = format!(
+          "SyntaxWarning: '{} {}' at position {}:{} is read as '{} ({})', add brackets to make it clear",
+          symbol,
+          divisor,
+          operator.position.human.line,
+          operator.position.human.column,
+          symbol,
+          divisor,
+        );
+        self.warnings.push(SyntaxWarning {
+          message,
+          position: operator.position,
+        });
+      } else if sign != Sign::Mul
+        && first.kind == TokenKind::Identifier
+        && Parser::several_letters(&product[0])
       {
-        token = Token::new(TokenKind::Multiply, &"", token.position.clone());
+        // `a/bc` divides by the one name `bc`, which is easily misread as `a/(b c)`
+        let message = format!(
+          "SyntaxWarning: '{}' at position {}:{} is read as one name rather than '{}', add a space or brackets to make it clear",
+          first.value,
+          first.position.human.line,
+          first.position.human.column,
+          first.value.chars().map(String::from).collect::<Vec<String>>().join(" "),
+        );
+        self.warnings.push(SyntaxWarning {
+          message,
+          position: first.position,
+        });
       }
+      // only extend a chain of the same operator, `a / b * c` is not `a / b / c`,
+      // though factors side by side always join a chain of products
       node = match *node {
-        AST::Term(n) => {
-          let mut n = n.clone();
-          n.term.push(self.factor());
+        AST::Term(mut n) if n.sign == sign && sign == Sign::Mul => {
+          n.term.extend(product);
           Box::new(AST::Term(n))
         }
-        _ => Box::new(AST::Term(Term {
-          sign: match token.kind {
-            TokenKind::Multiply => Sign::Mul,
-            TokenKind::Divide => Sign::Div,
-            _ => {
-              self.error(format!("SyntaxError: Unexpected {:?}", token.kind));
-            }
-          },
-          term: vec![node, self.factor()],
+        AST::Term(mut n) if n.sign == sign => {
+          n.term.push(Parser::multiply(product));
+          Box::new(AST::Term(n))
+        }
+        node if sign == Sign::Mul => {
+          let mut term = vec![Box::new(node)];
+          term.extend(product);
+          Box::new(AST::Term(Term { sign, term }))
+        }
+        node => Box::new(AST::Term(Term {
+          sign,
+          term: vec![Box::new(node), Parser::multiply(product)],
         })),
       }
     }
 
-    node
+    Ok(node)
   }
 
-  fn expr(&mut self) -> Box<AST> {
-    let mut node = self.term();
+  fn expr(&mut self) -> Result<Box<AST>, SyntaxError> {
+    let mut node = self.term()?;
 
     while [TokenKind::Add, TokenKind::Subtract, TokenKind::AddSubtract]
       .contains(&self.current_token.kind)
     {
-      let token = self.current_token.clone();
-      if token.kind == TokenKind::Add {
-        self.eat(TokenKind::Add);
-      } else if token.kind == TokenKind::Subtract {
-        self.eat(TokenKind::Subtract);
-      } else if token.kind == TokenKind::AddSubtract {
-        self.eat(TokenKind::AddSubtract);
-      }
+      let sign = match self.current_token.kind {
+        TokenKind::Add => Sign::Add,
+        TokenKind::Subtract => Sign::Sub,
+        _ => Sign::AddSub,
+      };
+      self.eat(self.current_token.kind)?;
+      // only extend a chain of the same operator, `a + b - c` is not `a + b + c`
       node = match *node {
-        AST::Expr(n) => {
+        AST::Expr(n) if n.sign == sign => {
           let mut n = n.clone();
-          n.expr.push(self.term());
+          n.expr.push(self.term()?);
           Box::new(AST::Expr(n))
         }
         _ => Box::new(AST::Expr(Expr {
-          sign: match token.kind {
-            TokenKind::Add => Sign::Add,
-            TokenKind::Subtract => Sign::Sub,
-            TokenKind::AddSubtract => Sign::AddSub,
-            _ => {
-              self.error(format!("SyntaxError: Unexpected {:?}", token.kind));
-            }
-          },
-          expr: vec![node, self.term()],
+          sign,
+          expr: vec![node, self.term()?],
         })),
       }
     }
 
-    node
+    Ok(node)
   }
 
-  fn identity(&mut self) -> Box<AST> {
+  fn identity(&mut self) -> Result<Box<AST>, SyntaxError> {
     let mut identity = Identity {
-      identity: vec![self.expr()],
+      identity: vec![self.expr()?],
     };
-    self.eat(TokenKind::Equals);
-    identity.identity.push(self.expr());
+    self.eat(TokenKind::Equals)?;
+    identity.identity.push(self.expr()?);
     while self.current_token.kind == TokenKind::Equals {
-      self.eat(TokenKind::Equals);
-      identity.identity.push(self.expr());
+      self.eat(TokenKind::Equals)?;
+      identity.identity.push(self.expr()?);
     }
-    Box::new(AST::Identity(identity))
+    Ok(Box::new(AST::Identity(identity)))
+  }
+
+  // `points: (1, 2), (2, 4.1)`, where `points` is only a keyword when a `:` follows it
+  fn is_points(&mut self) -> bool {
+    self.current_token.kind == TokenKind::Identifier
+      && self.current_token.value == "points"
+      && self
+        .peek()
+        .is_some_and(|next| next.kind == TokenKind::Colon)
   }
 
-  fn statement(&mut self) -> Box<AST> {
-    let identity = self.identity();
-    self.eat(TokenKind::Semicolon);
-    let mut command: Option<String> = None;
-    if self.current_token.kind == TokenKind::Command {
-      self.eat(TokenKind::Command);
-      command = Some(self.current_token.value.clone());
-      self.eat(TokenKind::Identifier);
+  fn points(&mut self) -> Result<Box<AST>, SyntaxError> {
+    self.eat(TokenKind::Identifier)?;
+    self.eat(TokenKind::Colon)?;
+    let mut points = vec![self.point()?];
+    while self.current_token.kind == TokenKind::Comma {
+      self.eat(TokenKind::Comma)?;
+      points.push(self.point()?);
     }
-    Box::new(AST::Statement(Statement {
-      command,
+    Ok(Box::new(AST::Points(Points { points })))
+  }
+
+  fn point(&mut self) -> Result<(Box<AST>, Box<AST>), SyntaxError> {
+    self.eat(TokenKind::LeftParen)?;
+    let point = self.nested(|p| {
+      let x = p.expr()?;
+      p.eat(TokenKind::Comma)?;
+      Ok((x, p.expr()?))
+    })?;
+    self.eat(TokenKind::RightParen)?;
+    Ok(point)
+  }
+
+  // a name followed by `=`, which is an option such as `width=2` when the `=` follows the name
+  // straight away, and otherwise starts the next statement such as `y = 2x;` on the same line
+  fn is_assignment(&mut self, option: bool) -> bool {
+    let end = self.current_token.span.end;
+    self.current_token.kind == TokenKind::Identifier
+      && self
+        .peek()
+        .is_some_and(|next| next.kind == TokenKind::Equals && (next.span.start == end) == option)
+  }
+
+  // text, a colour, or a token that can start a factor
+  fn is_argument(&self) -> bool {
+    [
+      TokenKind::Text,
+      TokenKind::Colour,
+      TokenKind::Add,
+      TokenKind::Subtract,
+      TokenKind::AddSubtract,
+      TokenKind::Root,
+      TokenKind::Number,
+      TokenKind::Identifier,
+      TokenKind::LeftParen,
+      TokenKind::Pipe,
+      TokenKind::LeftBrace,
+    ]
+    .contains(&self.current_token.kind)
+  }
+
+  fn option(&mut self) -> Result<(String, Argument), SyntaxError> {
+    let name = self.current_token.value.clone();
+    self.eat(TokenKind::Identifier)?;
+    self.eat(TokenKind::Equals)?;
+    Ok((name, self.argument()?))
+  }
+
+  // text, a colour, a factor or a range between two factors
+  fn argument(&mut self) -> Result<Argument, SyntaxError> {
+    let kind = self.current_token.kind;
+    if [TokenKind::Text, TokenKind::Colour].contains(&kind) {
+      let text = self.current_token.value.clone();
+      self.eat(kind)?;
+      return Ok(Argument::Text(text));
+    }
+    let from = self.factor()?;
+    if self.current_token.kind != TokenKind::Range {
+      return Ok(Argument::Expr(from));
+    }
+    self.eat(TokenKind::Range)?;
+    Ok(Argument::Range(from, self.factor()?))
+  }
+
+  fn command(&mut self) -> Result<Command, SyntaxError> {
+    let line = self.current_token.position.human.line;
+    self.eat(TokenKind::Command)?;
+    let name = self.current_token.value.clone();
+    self.eat(TokenKind::Identifier)?;
+    let mut arguments: Vec<Argument> = Vec::new();
+    let mut options: BTreeMap<String, Argument> = BTreeMap::new();
+    // the next line starts the next statement, and a `@`, `;` or anything else that cannot be an
+    // argument ends the command
+    while self.is_argument() && self.current_token.position.human.line == line {
+      if self.is_assignment(false) {
+        break;
+      } else if self.is_assignment(true) {
+        let (name, value) = self.option()?;
+        options.insert(name, value);
+      } else {
+        arguments.push(self.argument()?);
+      }
+    }
+    Ok(Command {
+      name,
+      arguments,
+      options,
+    })
+  }
+
+  fn statement(&mut self) -> Result<Box<AST>, SyntaxError> {
+    let identity = if self.is_points() {
+      self.points()?
+    } else {
+      self.identity()?
+    };
+    self.eat(TokenKind::Semicolon)?;
+    let mut commands: Vec<Command> = Vec::new();
+    while self.current_token.kind == TokenKind::Command {
+      commands.push(self.command()?);
+    }
+    Ok(Box::new(AST::Statement(Statement {
       statement: identity,
-    }))
+      commands,
+    })))
   }
 
-  fn statements(&mut self) -> Vec<Box<AST>> {
+  fn statements(&mut self) -> Result<Vec<Box<AST>>, SyntaxError> {
     let mut statements = Vec::new();
     while self.current_token.kind != TokenKind::EOF {
-      statements.push(self.statement());
+      statements.push(self.statement()?);
     }
-    statements
+    Ok(statements)
   }
 
-  pub fn parse(&mut self) -> Vec<Box<AST>> {
+  pub fn parse(&mut self) -> Result<Vec<Box<AST>>, SyntaxError> {
+    self.current_token = self.next_token()?;
     self.statements()
   }
+
+  // parse a lone expression, with no `=` or `;`
+  pub fn parse_expression(&mut self) -> Result<Box<AST>, SyntaxError> {
+    self.current_token = self.next_token()?;
+    let expr = self.expr()?;
+    self.eat(TokenKind::EOF)?;
+    Ok(expr)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use proptest::prelude::*;
+
+  // input that stops part way through a statement can be finished later
+  #[test]
+  fn incomplete() {
+    for input in ["y = 2x +", "y = 2x + 1", "y = (x", "y"] {
+      let error = Parser::new(input.to_string()).parse().unwrap_err();
+      assert!(error.incomplete, "{}", input);
+    }
+  }
+
+  // a mistake part way through is reported without panicking
+  #[test]
+  fn unexpected() {
+    for input in ["y = = 3;", "y = 3 );", "y = 3; 4 = ;", "y = $;"] {
+      let error = Parser::new(input.to_string()).parse().unwrap_err();
+      assert!(!error.incomplete, "{}", input);
+    }
+  }
+
+  // a chain of operators is only merged while the operator stays the same
+  #[test]
+  fn mixed_signs() {
+    let ast = Parser::new("a + b - c".to_string())
+      .parse_expression()
+      .unwrap();
+    assert_eq!(
+      *ast,
+      AST::Expr(Expr {
+        sign: Sign::Sub,
+        expr: vec![
+          Box::new(AST::Expr(Expr {
+            sign: Sign::Add,
+            expr: vec![
+              Box::new(AST::Variable("a".to_string())),
+              Box::new(AST::Variable("b".to_string())),
+            ],
+          })),
+          Box::new(AST::Variable("c".to_string())),
+        ],
+      })
+    );
+  }
+
+  // `±` and `+-` are the same operator, but a plus followed by a negative is not
+  #[test]
+  fn plus_minus() {
+    let parse = |input: &str| *Parser::new(input.to_string()).parse_expression().unwrap();
+    assert_eq!(parse("a ± b"), parse("a +- b"));
+    assert!(matches!(parse("a ± b"), AST::Expr(e) if e.sign == Sign::AddSub));
+    assert!(matches!(parse("a + -b"), AST::Expr(e) if e.sign == Sign::Add));
+  }
+
+  // known functions followed by brackets are calls, anything else multiplies
+  #[test]
+  fn calls() {
+    let parse = |input: &str| *Parser::new(input.to_string()).parse_expression().unwrap();
+    assert!(matches!(parse("sqrt(x, 2)"), AST::Call(c) if c.call.len() == 2));
+    assert!(matches!(parse("y(x + 1)"), AST::Term(t) if t.sign == Sign::Mul));
+  }
+
+  // textbook symbols read the same as their ascii forms
+  #[test]
+  fn unicode() {
+    let parse = |input: &str| *Parser::new(input.to_string()).parse_expression().unwrap();
+    assert_eq!(
+      parse("2x² − 3 × y ÷ 4 ± √(x + 1) + 2π"),
+      parse("2x^2 - 3 * y / 4 +- sqrt(x + 1) + 2π")
+    );
+    assert_eq!(parse("x⁻¹²"), parse("x^-12"));
+    assert_eq!(parse("πr²"), parse("π * r^2"));
+  }
+
+  // the forms that read differently by convention, each next to its bracketed meaning
+  #[test]
+  fn precedence() {
+    let parse = |input: &str| *Parser::new(input.to_string()).parse_expression().unwrap();
+    for (input, meaning) in [
+      ("-x^2", "-(x^2)"),
+      ("-2^2", "-(2^2)"),
+      ("2x^2", "2(x^2)"),
+      ("2^-x", "2^(-x)"),
+      ("a^b^c", "a^(b^c)"),
+      ("1/2x", "1/(2x)"),
+      ("1/2(x + 1)", "1/(2(x + 1))"),
+      ("a/b c", "a/(b c)"),
+      ("2x/3y", "(2x)/(3y)"),
+      ("a/b*c", "(a/b)*c"),
+      ("a/b/c", "(a/b)/c"),
+      ("a*2x", "a*2*x"),
+      ("x mod 2y", "x mod (2y)"),
+      ("-2x", "(-2)x"),
+      ("n!^2", "(n!)^2"),
+      ("a - b + c", "(a - b) + c"),
+    ] {
+      assert_eq!(parse(input), parse(meaning), "{}", input);
+    }
+    assert_ne!(parse("(-x)^2"), parse("-x^2"));
+  }
+
+  // a zero coefficient written before x is not a hex literal
+  #[test]
+  fn zero_x() {
+    assert_eq!(
+      *Parser::new("y = 0x + 1;".to_string()).parse().unwrap()[0],
+      *Parser::new("y = 0 * x + 1;".to_string()).parse().unwrap()[0]
+    );
+  }
+
+  // dividing by factors side by side is allowed, but pointed out
+  #[test]
+  fn ambiguous_division() {
+    let warnings = |input: &str| {
+      let mut parser = Parser::new(input.to_string());
+      parser.parse_expression().unwrap();
+      parser.warnings().to_vec()
+    };
+    let warning = &warnings("y + 1 ÷ 2x")[0];
+    assert_eq!(
+      warning.message,
+      "SyntaxWarning: '÷ 2x' at position 1:7 is read as '÷ (2x)', add brackets to make it clear"
+    );
+    assert_eq!(warnings("a/b c mod 2y").len(), 2);
+    // a name of several letters is one variable, which is pointed out since `b c` was likely meant
+    let warning = &warnings("a/bc")[0];
+    assert_eq!(
+      warning.message,
+      "SyntaxWarning: 'bc' at position 1:3 is read as one name rather than 'b c', add a space or brackets to make it clear"
+    );
+    assert_eq!(warnings("a / xy^2 mod bc").len(), 2);
+    for input in ["1/(2x)", "2x/3", "a/b*c", "2/pi", "a/(bc)", "a/b"] {
+      assert!(warnings(input).is_empty(), "{}", input);
+    }
+  }
+
+  // nesting too deep for the stack is an error rather than a crash
+  #[test]
+  fn deep() {
+    for input in [
+      "(".repeat(100000),
+      "-".repeat(100000) + "x",
+      "x^".repeat(100000) + "x",
+    ] {
+      let error = Parser::new(input).parse_expression().unwrap_err();
+      assert!(error.message.contains("Too deeply nested"));
+    }
+    let input = "(".repeat(100) + "x" + &")".repeat(100);
+    assert!(Parser::new(input).parse_expression().is_ok());
+  }
+
+  // the text of a token of every kind, so generated input reaches every rule of the grammar
+  fn token() -> impl Strategy<Value = &'static str> {
+    proptest::sample::select(vec![
+      "(", ")", "-", "−", "+", "±", "+-", "*", "×", "/", "÷", "^", "√", "=", "<", ">", "<=", "≥",
+      ",", ";", ":", "@", "|", "!", "{", "}", "x", "y", "sin", "mod", "π", "e", "2", ".5", "1e3",
+      "0x1F", "1_0", "²", "⁻¹", "# note\n", "\n",
+    ])
+  }
+
+  proptest! {
+    // any sequence of tokens gives a tree or a syntax error, and never panics
+    #[test]
+    fn never_panics(tokens in prop::collection::vec((token(), prop::bool::ANY), 0..40)) {
+      let input: String = tokens
+        .iter()
+        .map(|(token, space)| if *space { format!("{} ", token) } else { token.to_string() })
+        .collect();
+      let results = [
+        Parser::new(input.clone()).parse().map(|_| ()),
+        Parser::new(input.clone()).parse_expression().map(|_| ()),
+      ];
+      for error in results.into_iter().filter_map(Result::err) {
+        prop_assert!(error.message.starts_with("SyntaxError"), "{}", error.message);
+        prop_assert!(error.position.machine <= input.len());
+      }
+    }
+  }
+
+  // `|x|` and `n!` are calls, and a `|` closes the innermost one unless it starts a factor
+  #[test]
+  fn abs_and_factorial() {
+    let parse = |input: &str| *Parser::new(input.to_string()).parse_expression().unwrap();
+    assert_eq!(parse("|x - 1|"), parse("abs(x - 1)"));
+    assert_eq!(parse("2|x||y|"), parse("2 * abs(x) * abs(y)"));
+    assert_eq!(parse("||x| - |y||"), parse("abs(abs(x) - abs(y))"));
+    assert_eq!(parse("n!^2"), parse("factorial(n)^2"));
+    assert_eq!(parse("-3!!"), parse("-factorial(factorial(3))"));
+    assert!(Parser::new("|x".to_string()).parse_expression().is_err());
+  }
+
+  // `mod` is an operator between factors, with the same precedence as `*` and `/`
+  #[test]
+  fn modulus() {
+    let parse = |input: &str| *Parser::new(input.to_string()).parse_expression().unwrap();
+    assert!(matches!(parse("x mod 3"), AST::Term(t) if t.sign == Sign::Mod));
+    assert!(matches!(parse("1 + 2x mod 3"), AST::Expr(e) if e.sign == Sign::Add));
+  }
+
+  // a command takes the factors after it on the same line
+  #[test]
+  fn arguments() {
+    let statements = Parser::new("y = x^2; @tangent -1 (1 + 2)\ny = 3; @draw\n-y = x;".to_string())
+      .parse()
+      .unwrap();
+    let AST::Statement(ref s) = *statements[0] else {
+      panic!("expected a statement");
+    };
+    assert_eq!(s.commands.len(), 1);
+    assert_eq!(s.commands[0].name, "tangent");
+    assert_eq!(s.commands[0].arguments.len(), 2);
+    assert!(
+      matches!(s.commands[0].arguments[0], Argument::Expr(ref e) if matches!(**e, AST::Unary(ref u) if u.sign == Sign::Sub))
+    );
+    assert_eq!(statements.len(), 3);
+    assert!(matches!(*statements[1], AST::Statement(ref s) if s.commands[0].arguments.is_empty()));
+  }
+
+  // a command ends where the next statement on the same line begins
+  #[test]
+  fn same_line() {
+    let statements = Parser::new("y = x; @draw y = 2x; @draw @tangent 1 y = 3; @draw".to_string())
+      .parse()
+      .unwrap();
+    assert_eq!(statements.len(), 3);
+    for statement in &statements {
+      let AST::Statement(ref s) = **statement else {
+        panic!("expected a statement");
+      };
+      assert!(s.has("draw"));
+      assert!(s.commands.iter().all(|c| c.options.is_empty()));
+    }
+    let AST::Statement(ref s) = *statements[1] else {
+      panic!("expected a statement");
+    };
+    assert_eq!(s.commands[1].arguments.len(), 1);
+    assert!(Parser::new("y = x; @draw;".to_string()).parse().is_err());
+  }
+
+  // one token of lookahead tells an option from the next statement, by whether a space comes
+  // before the `=`, and a lexing error found while looking ahead is still the one returned
+  #[test]
+  fn lookahead() {
+    let statements = Parser::new("y = x; @draw width=2 y = 3x; @draw".to_string())
+      .parse()
+      .unwrap();
+    assert_eq!(statements.len(), 2);
+    let AST::Statement(ref s) = *statements[0] else {
+      panic!("expected a statement");
+    };
+    assert!(s.commands[0].options.contains_key("width"));
+    let error = Parser::new("points ?".to_string()).parse().unwrap_err();
+    assert_eq!(error.message, "SyntaxError: Unexpected '?' at position 1:8");
+  }
+
+  // a statement can have several commands, each taking what follows it up to the next `@`
+  #[test]
+  fn commands() {
+    let statements = Parser::new(
+      "y = x^2; @draw style=dotted @tangent 1 2 @view x=-5..5 \"wide\"\n  @turning\ny = 3;"
+        .to_string(),
+    )
+    .parse()
+    .unwrap();
+    assert_eq!(statements.len(), 2);
+    let AST::Statement(ref s) = *statements[0] else {
+      panic!("expected a statement");
+    };
+    let names: Vec<&str> = s.commands.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["draw", "tangent", "view", "turning"]);
+    assert!(s.has("tangent") && !s.has("intercepts"));
+    assert_eq!(s.commands[1].arguments.len(), 2);
+    let view = &s.commands[2];
+    assert!(matches!(
+      view.options["x"],
+      Argument::Range(ref from, ref to) if matches!(**from, AST::Unary(_)) && **to == AST::Number(5f64)
+    ));
+    assert_eq!(view.arguments, vec![Argument::Text("wide".to_string())]);
+    assert!(Parser::new("y = x; @".to_string()).parse().is_err());
+    assert!(Parser::new("y = x; @view x=1..;".to_string())
+      .parse()
+      .is_err());
+  }
+
+  // `name=value` after a command is an option rather than an argument, whatever order they come in
+  #[test]
+  fn options() {
+    let statements = Parser::new(
+      "y = x; @draw color=#f00 style=dashed 2 width=2 label=\"profit\"\ny = 3;".to_string(),
+    )
+    .parse()
+    .unwrap();
+    let AST::Statement(ref s) = *statements[0] else {
+      panic!("expected a statement");
+    };
+    let options: Vec<(&str, String)> = s.commands[0]
+      .options
+      .iter()
+      .map(|(k, v)| (k.as_str(), v.word().unwrap()))
+      .collect();
+    assert_eq!(
+      options,
+      vec![
+        ("color", "#f00".to_string()),
+        ("label", "profit".to_string()),
+        ("style", "dashed".to_string()),
+        ("width", "2".to_string())
+      ]
+    );
+    assert_eq!(
+      s.commands[0].arguments,
+      vec![Argument::Expr(Box::new(AST::Number(2f64)))]
+    );
+    assert_eq!(statements.len(), 2);
+    assert!(Parser::new("y = x; @draw width=;".to_string())
+      .parse()
+      .is_err());
+  }
+
+  // a list of points is read in place of an equation, while `points` on its own is a variable
+  #[test]
+  fn points() {
+    let statements = Parser::new("points: (1, 2), (2, -4.1); @fit linear\npoints = 3;".to_string())
+      .parse()
+      .unwrap();
+    let AST::Statement(ref s) = *statements[0] else {
+      panic!("expected a statement");
+    };
+    assert!(matches!(*s.statement, AST::Points(ref p) if p.points.len() == 2));
+    assert_eq!(
+      s.commands[0].arguments,
+      vec![Argument::Expr(Box::new(AST::Variable(
+        "linear".to_string()
+      )))]
+    );
+    assert!(
+      matches!(*statements[1], AST::Statement(ref s) if matches!(*s.statement, AST::Identity(_)))
+    );
+    assert!(Parser::new("points: (1, 2), (3);".to_string())
+      .parse()
+      .is_err());
+    assert!(Parser::new("points: ;".to_string()).parse().is_err());
+  }
+
+  // cases are tried in order and the last may leave out its condition
+  #[test]
+  fn piecewise() {
+    let parse = |input: &str| Parser::new(input.to_string()).parse_expression();
+    let AST::Piecewise(p) = *parse("{x < 0: -x; x >= 2: 2; x}").unwrap() else {
+      panic!("expected a piecewise");
+    };
+    assert_eq!(p.piecewise.len(), 2);
+    assert_eq!(p.otherwise, Some(Box::new(AST::Variable("x".to_string()))));
+    assert!(
+      matches!(*p.piecewise[1].0, AST::Compare(ref c) if c.relation == Relation::GreaterEqual)
+    );
+    assert!(parse("{x}").is_err());
+    assert!(parse("{x < 0}").is_err());
+    assert!(parse("{x < 0: 1; 2; 3}").is_err());
+  }
 }
@@ -0,0 +1,146 @@
+use super::parser::{Argument, AST};
+use serde::{Deserialize, Serialize};
+
+// bumped whenever the shape of the AST changes, so old documents are rejected rather than misread
+pub const VERSION: u32 = 5;
+
+// parsed statements saved as json, so they can be cached or built by other tools
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+  pub version: u32,
+  pub statements: Vec<AST>,
+}
+
+impl Document {
+  pub fn new(statements: Vec<AST>) -> Document {
+    Document {
+      version: VERSION,
+      statements,
+    }
+  }
+
+  pub fn to_json(&self) -> String {
+    serde_json::to_string_pretty(self).unwrap()
+  }
+
+  pub fn from_json(input: &str) -> Result<Document, String> {
+    // check the version first, a document from another version may not match the AST at all
+    let value: serde_json::Value =
+      serde_json::from_str(input).map_err(|e| format!("Invalid document: {}", e))?;
+    match value.get("version").and_then(|v| v.as_u64()) {
+      Some(v) if v == VERSION as u64 => {}
+      Some(v) => {
+        return Err(format!(
+          "Unsupported document version {}, expected {}",
+          v, VERSION
+        ))
+      }
+      None => return Err("Invalid document: missing version".to_string()),
+    }
+    let document: Document =
+      serde_json::from_value(value).map_err(|e| format!("Invalid document: {}", e))?;
+    for statement in &document.statements {
+      check(statement).map_err(|e| format!("Invalid document: {}", e))?;
+    }
+    Ok(document)
+  }
+}
+
+// the parser never builds an empty sum, product or call, or an identity with one side, and the
+// rest of the crate relies on that, so a document must not either
+fn check(ast: &AST) -> Result<(), String> {
+  fn all<'a>(mut nodes: impl Iterator<Item = &'a Box<AST>>) -> Result<(), String> {
+    nodes.try_for_each(|n| check(n))
+  }
+  match ast {
+    AST::Expr(e) if e.expr.is_empty() => Err("empty Expr".to_string()),
+    AST::Term(t) if t.term.is_empty() => Err("empty Term".to_string()),
+    AST::Call(c) if c.call.is_empty() => Err(format!("call to {} without arguments", c.name)),
+    AST::Identity(i) if i.identity.len() < 2 => Err("Identity with fewer than 2 sides".to_string()),
+    AST::Expr(e) => all(e.expr.iter()),
+    AST::Term(t) => all(t.term.iter()),
+    AST::Call(c) => all(c.call.iter()),
+    AST::Identity(i) => all(i.identity.iter()),
+    AST::Index(i) => all([&i.index.0, &i.index.1].into_iter()),
+    AST::Unary(u) => check(&u.unary),
+    AST::Compare(c) => all([&c.compare.0, &c.compare.1].into_iter()),
+    AST::Piecewise(p) => all(
+      p.piecewise
+        .iter()
+        .flat_map(|(condition, value)| [condition, value])
+        .chain(p.otherwise.iter()),
+    ),
+    AST::Points(p) => all(p.points.iter().flat_map(|(x, y)| [x, y])),
+    AST::Statement(s) => {
+      check(&s.statement)?;
+      for command in &s.commands {
+        for argument in command.arguments.iter().chain(command.options.values()) {
+          match argument {
+            Argument::Expr(e) => check(e)?,
+            Argument::Range(from, to) => all([from, to].into_iter())?,
+            Argument::Text(_) => {}
+          }
+        }
+      }
+      Ok(())
+    }
+    AST::Variable(_) | AST::Number(_) => Ok(()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::syntax::parser::Parser;
+
+  // every kind of node survives a trip through json
+  #[test]
+  fn round_trip() {
+    let statements: Vec<AST> = Parser::new(
+      "y = -(x + 1)^2 / 3 - 2x; @draw\r\ny = x^2; @tangent -1 color=#00f label=\"slope\" @view x=-5..5\r\na = 4 = b;\r\ny = {x <= 0: |x|!; x mod 3};".to_string(),
+    )
+    .parse()
+    .unwrap()
+    .into_iter()
+    .map(|s| *s)
+    .collect();
+    let document = Document::new(statements);
+    assert_eq!(Document::from_json(&document.to_json()), Ok(document));
+  }
+
+  // a product with no factors is refused rather than left to panic when printed
+  #[test]
+  fn empty() {
+    let document = format!(
+      r#"{{ "version": {}, "statements": [{{ "Term": {{ "sign": "Mul", "term": [] }} }}] }}"#,
+      VERSION
+    );
+    assert!(Document::from_json(&document)
+      .unwrap_err()
+      .contains("empty Term"));
+  }
+
+  // an identity needs two sides, even inside a statement
+  #[test]
+  fn sides() {
+    let statement = Parser::new("y = x;".to_string()).parse().unwrap()[0].clone();
+    let mut document = Document::new(vec![*statement]);
+    if let AST::Statement(s) = &mut document.statements[0] {
+      if let AST::Identity(i) = s.statement.as_mut() {
+        i.identity.truncate(1);
+      }
+    }
+    assert!(Document::from_json(&document.to_json())
+      .unwrap_err()
+      .contains("fewer than 2 sides"));
+  }
+
+  // documents written by another version are refused
+  #[test]
+  fn version() {
+    let document = r#"{ "version": 0, "statements": [] }"#;
+    assert!(Document::from_json(document)
+      .unwrap_err()
+      .contains("version 0"));
+  }
+}
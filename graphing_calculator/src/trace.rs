@@ -0,0 +1,606 @@
+use graphing_calculator::{
+  curve, draw_panels, expand, fit, intercepts, intersections, pretty, tangents, to_latex,
+  to_mathml, turning_points, unique, Argument, Check, Command, Error, Figure, Model, Panel, Parser,
+  Points, Quadratic, Statement, Step, Style, Working, AST,
+};
+use serde_json::{json, Value};
+use std::path::Path;
+
+// the statement commands that add a curve to the plot
+pub const DRAW_COMMANDS: [&str; 4] = ["draw", "intercepts", "turning", "tangent"];
+
+// report input that parsed but may not mean what was intended, away from the results
+pub fn warn(parser: &Parser) {
+  for warning in parser.warnings() {
+    eprintln!("{}", warning);
+  }
+}
+
+pub fn format_points(points: &[(f64, f64)]) -> String {
+  points
+    .iter()
+    .map(|(x, y)| format!("({:.3}, {:.3})", x, y))
+    .collect::<Vec<String>>()
+    .join(", ")
+}
+
+// an expression as both readable text and its syntax tree
+pub fn expression(ast: &AST) -> Value {
+  json!({
+    "text": pretty(ast.clone()),
+    "latex": to_latex(ast),
+    "mathml": to_mathml(ast),
+    "ast": ast,
+  })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+  Text,
+  // expressions written as latex, ready to paste into a document
+  Latex,
+  // one json document per command instead of text
+  Json,
+}
+
+// where the working and results of a command are written
+pub struct Output {
+  // only print results, not the steps taken to reach them
+  pub quiet: bool,
+  pub format: Format,
+}
+
+impl Output {
+  // an expression in the notation of the output format
+  pub fn render(&self, ast: &AST) -> String {
+    match self.format {
+      Format::Latex => to_latex(ast),
+      _ => pretty(ast.clone()),
+    }
+  }
+
+  // an argument of a command as it was written, with its expressions rendered
+  pub fn argument(&self, argument: &Argument) -> String {
+    match argument {
+      Argument::Expr(e) => self.render(e),
+      Argument::Text(text) => format!("\"{}\"", text),
+      Argument::Range(from, to) => format!("{}..{}", self.render(from), self.render(to)),
+    }
+  }
+
+  // a line of working, hidden when quiet
+  pub fn step(&self, label: &str, text: &str) {
+    if !self.quiet && self.format != Format::Json {
+      println!("{:<10} {}", label.to_string() + ":", text);
+    }
+  }
+
+  pub fn ast(&self, label: &str, ast: &AST) {
+    self.step(label, &self.render(ast));
+  }
+
+  pub fn quadratic(&self, quad: &Quadratic) {
+    self.step(
+      "quadratic",
+      &format!(
+        "\n  a: {}\n  b: {}\n  c: {}",
+        self.render(&quad.a),
+        self.render(&quad.b),
+        self.render(&quad.c)
+      ),
+    );
+  }
+
+  // each step of solving a statement
+  pub fn working(&self, working: &Working) {
+    self.ast("expanded", &working.expanded);
+    self.quadratic(&working.quadratic);
+    self.ast("solved", &working.solved);
+    self.ast("expanded", &working.solution);
+  }
+
+  // a worked solution, shown even when quiet since it was asked for
+  pub fn worked(&self, steps: &[Step]) {
+    if self.format != Format::Json {
+      for (i, step) in steps.iter().enumerate() {
+        println!("{:<10} {}", format!("step {}:", i + 1), step.rule);
+        println!("{:<10} {}", "", self.render(&step.ast));
+      }
+    }
+  }
+
+  // separate one block of working from the next
+  pub fn gap(&self) {
+    if !self.quiet && self.format != Format::Json {
+      println!();
+    }
+  }
+
+  // a result, always shown
+  pub fn result(&self, label: &str, text: &str) {
+    if self.format != Format::Json {
+      println!("{:<10} {}", label.to_string() + ":", text);
+    }
+  }
+
+  // each root put back into the equation, or why that could not be done
+  pub fn verified(&self, var: &str, checks: &Result<Option<Vec<Check>>, String>) {
+    let checks = match checks {
+      Ok(Some(checks)) => checks,
+      Ok(None) => {
+        return self.result(
+          "verify",
+          "the roots depend on other variables, so cannot be checked",
+        )
+      }
+      Err(e) => return self.result("verify", e),
+    };
+    if checks.is_empty() {
+      self.result("verify", "there are no real roots to check");
+    }
+    for check in checks {
+      let mut text = format!("{} = {}, residual {:e}", var, check.root, check.residual);
+      if !check.satisfies() {
+        text += ", does not satisfy the equation";
+      }
+      if !check.precise() {
+        text += &format!(", simplifying moved it by {:e}", check.drift);
+      }
+      self.result("verify", &text);
+    }
+  }
+
+  // the whole result of a command, only shown as json
+  pub fn document(&self, document: &Value) {
+    if self.format == Format::Json {
+      println!("{}", serde_json::to_string_pretty(document).unwrap());
+    }
+  }
+}
+
+pub fn verification(checks: &Result<Option<Vec<Check>>, String>) -> Value {
+  match checks {
+    Ok(Some(checks)) => checks
+      .iter()
+      .map(|check| {
+        json!({
+          "root": check.root,
+          "residual": check.residual,
+          "drift": check.drift,
+          "sound": check.sound(),
+        })
+      })
+      .collect(),
+    Ok(None) => Value::Null,
+    Err(e) => json!({ "error": e }),
+  }
+}
+
+pub fn worked(steps: &[Step]) -> Value {
+  steps
+    .iter()
+    .map(|step| json!({ "rule": step.rule, "expression": expression(&step.ast) }))
+    .collect()
+}
+
+// every step of solving a statement, each expression written out in full
+pub fn document(working: &Working) -> Value {
+  json!({
+    "original": expression(&working.original),
+    "expanded": expression(&working.expanded),
+    "quadratic": {
+      "a": expression(&working.quadratic.a),
+      "b": expression(&working.quadratic.b),
+      "c": expression(&working.quadratic.c),
+    },
+    "solved": expression(&working.solved),
+    "solution": expression(&working.solution),
+    "roots": working.roots(),
+  })
+}
+
+// solve a statement for var, printing each step
+pub fn solve(ast: &AST, var: &str, out: &Output) -> Result<Working, Error> {
+  let working = graphing_calculator::working(ast, var)?;
+  out.working(&working);
+  Ok(working)
+}
+
+// the ends of `MIN..MAX`, when both are numbers and MIN is the smaller
+fn range(argument: &Argument) -> Option<(f64, f64)> {
+  let Argument::Range(from, to) = argument else {
+    return None;
+  };
+  match (expand(*from.clone()), expand(*to.clone())) {
+    (AST::Number(from), AST::Number(to)) if from < to => Some((from, to)),
+    _ => None,
+  }
+}
+
+// the names of the commands of a statement, in order
+fn names(statement: &Statement) -> Vec<&str> {
+  statement.commands.iter().map(|c| c.name.as_str()).collect()
+}
+
+// the labels of two curves and the points where they cross
+pub type Crossing = (String, String, Vec<(f64, f64)>);
+
+// the curves and points of interest collected from drawn statements
+pub struct Plot {
+  // the figure given on the command line, which each panel starts from
+  base: Figure,
+  pub figure: Figure,
+  // the caption given to this panel by `@panel`
+  pub title: Option<String>,
+  // the panels finished before this one, each drawn from a plot of its own
+  pub panels: Vec<Plot>,
+  pub draws: Vec<(AST, String, Style)>,
+  // tangent and normal lines, measured points and curves fitted to them, drawn but left out of
+  // the search for intersections
+  pub overlays: Vec<(AST, String, Style)>,
+  pub points: Vec<(f64, f64)>,
+  // the working of each drawn statement, for json output
+  pub statements: Vec<Value>,
+}
+
+impl Plot {
+  pub fn new(figure: Figure) -> Plot {
+    Plot {
+      base: figure.clone(),
+      figure,
+      title: None,
+      panels: Vec::new(),
+      draws: Vec::new(),
+      overlays: Vec::new(),
+      points: Vec::new(),
+      statements: Vec::new(),
+    }
+  }
+
+  // the style asked for by the given options, reporting and leaving out any that are invalid
+  fn style<'a>(options: impl Iterator<Item = (&'a String, &'a Argument)>, out: &Output) -> Style {
+    let mut style = Style::default();
+    for (name, value) in options {
+      if let Err(e) = style.set(name, value) {
+        out.result("style", &e);
+      }
+    }
+    style
+  }
+
+  // finish the panel being drawn, unless nothing has been added to it yet, and start the next with
+  // the title and viewport given to `@panel`
+  fn panel(&mut self, command: &Command, out: &Output) {
+    if !self.statements.is_empty() {
+      let mut next = Plot::new(self.base.clone());
+      std::mem::swap(self, &mut next);
+      self.panels = std::mem::take(&mut next.panels);
+      self.panels.push(next);
+    }
+    let mut figure = self.base.clone();
+    let mut title: Option<String> = None;
+    for argument in command.arguments.iter() {
+      match argument {
+        Argument::Text(text) if title.is_none() => title = Some(text.clone()),
+        _ => out.result(
+          "panel",
+          &format!(
+            "{} is not a title, expected one in quotes such as @panel \"sine\"",
+            out.argument(argument)
+          ),
+        ),
+      }
+    }
+    for (name, value) in command.options.iter() {
+      let axis = match name.as_str() {
+        "x" => &mut figure.x,
+        "y" => &mut figure.y,
+        _ => {
+          out.result(
+            "panel",
+            &format!("{} is not an option, expected x or y", name),
+          );
+          continue;
+        }
+      };
+      match range(value) {
+        Some(range) => *axis = range,
+        None => out.result(
+          "panel",
+          &format!(
+            "{} is not a range, expected {}=MIN..MAX such as {}=-5..5",
+            out.argument(value),
+            name,
+            name
+          ),
+        ),
+      }
+    }
+    self.figure = figure;
+    self.title = title;
+  }
+
+  // solve a statement for y and add it to the plot, if any of its commands draws it, failing when
+  // it cannot be drawn
+  pub fn statement(&mut self, statement: Statement, out: &Output) -> Result<bool, Error> {
+    if let Some(command) = statement.commands.iter().find(|c| c.name == "panel") {
+      self.panel(command, out);
+    }
+    if let AST::Points(points) = *statement.statement.clone() {
+      self.scatter(points, &statement, out);
+      return Ok(true);
+    }
+    if !DRAW_COMMANDS.iter().any(|name| statement.has(name)) {
+      return Ok(false);
+    }
+    let original = AST::Statement(statement.clone());
+    let working = curve(&original)?;
+    out.working(&working);
+    let style = Plot::style(Style::options(&statement.commands), out);
+    let label = style
+      .label
+      .clone()
+      .unwrap_or_else(|| pretty(expand(original.clone())));
+    let expr = working.solution.clone();
+    let mut document = document(&working);
+    document["commands"] = json!(names(&statement));
+    let mut found_tangents: Vec<Value> = Vec::new();
+    for command in statement.commands.iter() {
+      match command.name.as_str() {
+        "intercepts" => {
+          let found = intercepts(&expr, self.figure.x);
+          out.result("intercepts", &format_points(&found));
+          document["intercepts"] = json!(found);
+          self.points.extend(found);
+        }
+        "turning" => {
+          let found = turning_points(&expr, self.figure.x);
+          out.result("turning", &format_points(&found));
+          document["turning"] = json!(found);
+          self.points.extend(found);
+        }
+        "tangent" => found_tangents.extend(self.tangents(&expr, command, out)),
+        _ => {}
+      }
+    }
+    if statement.has("tangent") {
+      document["tangents"] = json!(found_tangents);
+    }
+    self.statements.push(document);
+    self.draws.push((expr, label, style));
+    out.gap();
+    Ok(true)
+  }
+
+  // plot measured points, fitting a curve to them for each model given to `@fit`
+  fn scatter(&mut self, points: Points, statement: &Statement, out: &Output) {
+    let mut data: Vec<(f64, f64)> = Vec::new();
+    for (x, y) in points.points {
+      match (expand(*x), expand(*y)) {
+        (AST::Number(x), AST::Number(y)) => data.push((x, y)),
+        (x, y) => out.result(
+          "points",
+          &format!(
+            "({}, {}) is not a pair of numbers",
+            out.render(&x),
+            out.render(&y)
+          ),
+        ),
+      }
+    }
+    let style = Plot::style(Style::options(&statement.commands), out);
+    let mut document = json!({
+      "original": expression(&AST::Statement(statement.clone())),
+      "commands": names(statement),
+      "points": data,
+    });
+    if statement.has("fit") {
+      let fits: Vec<Value> = statement
+        .commands
+        .iter()
+        .filter(|c| c.name == "fit")
+        .flat_map(|c| self.fits(&data, &c.arguments, out))
+        .collect();
+      document["fits"] = json!(fits);
+    }
+    self.statements.push(document);
+    self.overlays.push((
+      AST::Points(Points {
+        points: data
+          .iter()
+          .map(|&(x, y)| (Box::new(AST::Number(x)), Box::new(AST::Number(y))))
+          .collect(),
+      }),
+      style
+        .label
+        .clone()
+        .unwrap_or_else(|| format!("{} points", data.len())),
+      style,
+    ));
+    out.gap();
+  }
+
+  // the curve of each model named after `@fit` closest to the points
+  fn fits(&mut self, data: &[(f64, f64)], arguments: &[Argument], out: &Output) -> Vec<Value> {
+    if arguments.is_empty() {
+      out.result("fit", "expected a model, such as @fit linear");
+    }
+    let mut found: Vec<Value> = Vec::new();
+    for argument in arguments {
+      let model = match argument {
+        Argument::Expr(e) => match e.as_ref() {
+          AST::Variable(name) => Model::from(name),
+          _ => None,
+        },
+        _ => None,
+      };
+      let Some(model) = model else {
+        out.result(
+          "fit",
+          &format!(
+            "{} is not a model, expected linear, quadratic or exp",
+            out.argument(argument)
+          ),
+        );
+        continue;
+      };
+      let Some(f) = fit(data, model) else {
+        out.result(
+          "fit",
+          &format!("cannot fit a {} curve to these points", model.name()),
+        );
+        continue;
+      };
+      out.result(
+        "fit",
+        &format!(
+          "{}: {}, r² = {:.4}",
+          model.name(),
+          out.render(&f.curve),
+          f.r2
+        ),
+      );
+      self
+        .overlays
+        .push((f.curve.clone(), pretty(f.curve.clone()), Style::default()));
+      found.push(json!({
+        "model": model.name(),
+        "coefficients": f.coefficients,
+        "curve": expression(&f.curve),
+        "r2": f.r2,
+      }));
+    }
+    found
+  }
+
+  // the tangent and normal at each x given to `@tangent`, adding both lines to the plot in the
+  // style of its options
+  fn tangents(&mut self, curve: &AST, command: &Command, out: &Output) -> Vec<Value> {
+    let arguments = &command.arguments;
+    let style = Plot::style(command.options.iter(), out);
+    if arguments.is_empty() {
+      out.result("tangent", "expected the x of a point, such as @tangent 2");
+    }
+    let mut found: Vec<Value> = Vec::new();
+    for argument in arguments {
+      let x = match argument {
+        Argument::Expr(e) => match expand(*e.clone()) {
+          AST::Number(x) => Some(x),
+          _ => None,
+        },
+        _ => None,
+      };
+      let Some(x) = x else {
+        out.result(
+          "tangent",
+          &format!("{} is not a number", out.argument(argument)),
+        );
+        continue;
+      };
+      for t in tangents(curve, x) {
+        out.result(
+          "tangent",
+          &format!(
+            "at {}: {}",
+            format_points(&[t.point]),
+            out.render(&t.tangent)
+          ),
+        );
+        out.result("normal", &out.render(&t.normal));
+        for line in [&t.tangent, &t.normal] {
+          let label = style.label.clone().unwrap_or_else(|| pretty(line.clone()));
+          self.overlays.push((line.clone(), label, style.clone()));
+        }
+        self.points.push(t.point);
+        found.push(json!({
+          "point": t.point,
+          "gradient": t.gradient,
+          "tangent": expression(&t.tangent),
+          "normal": expression(&t.normal),
+        }));
+      }
+    }
+    found
+  }
+
+  // every point where two of the drawn curves cross, with the labels of both curves
+  pub fn intersections(&self, out: &Output) -> Vec<Crossing> {
+    let mut crossings: Vec<Crossing> = Vec::new();
+    for i in 0..self.draws.len() {
+      for j in i + 1..self.draws.len() {
+        let found = intersections(&self.draws[i].0, &self.draws[j].0, self.figure.x);
+        if !found.is_empty() {
+          out.result(
+            "intersect",
+            &format!(
+              "{} and {}: {}",
+              self.draws[i].1,
+              self.draws[j].1,
+              format_points(&found)
+            ),
+          );
+          crossings.push((self.draws[i].1.clone(), self.draws[j].1.clone(), found));
+        }
+      }
+    }
+    crossings
+  }
+
+  // the curves and points of this panel to draw, with a json document of them
+  fn finish(&self, out: &Output) -> (Panel, Value) {
+    let crossings = self.intersections(out);
+    let mut points = self.points.clone();
+    for (_, _, found) in crossings.iter() {
+      points.extend(found);
+    }
+    let panel = Panel {
+      title: self.title.clone(),
+      x: self.figure.x,
+      y: self.figure.y,
+      curves: self
+        .draws
+        .iter()
+        .chain(self.overlays.iter())
+        .cloned()
+        .collect(),
+      points: unique(points),
+    };
+    let document = json!({
+      "statements": self.statements,
+      "intersections": crossings
+        .iter()
+        .map(|(a, b, found)| json!({ "curves": [a, b], "points": found }))
+        .collect::<Vec<Value>>(),
+    });
+    (panel, document)
+  }
+
+  // draw every curve, each panel beside the last, returning a json document of what was drawn
+  pub fn draw(
+    &self,
+    file: &String,
+    title: &str,
+    out: &Output,
+  ) -> Result<Value, Box<dyn std::error::Error>> {
+    let (panels, mut documents): (Vec<Panel>, Vec<Value>) = self
+      .panels
+      .iter()
+      .chain(std::iter::once(self))
+      .map(|plot| plot.finish(out))
+      .unzip();
+    let mut document = json!({ "output": file, "title": title });
+    if let [only] = &mut documents[..] {
+      document["statements"] = only["statements"].take();
+      document["intersections"] = only["intersections"].take();
+    } else {
+      for (document, panel) in documents.iter_mut().zip(&panels) {
+        document["title"] = json!(panel.title);
+        document["x"] = json!(panel.x);
+        document["y"] = json!(panel.y);
+      }
+      document["panels"] = json!(documents);
+    }
+    draw_panels(Path::new(file), title, panels, &self.base)?;
+    Ok(document)
+  }
+}
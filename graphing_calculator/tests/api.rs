@@ -0,0 +1,105 @@
+use graphing_calculator::{
+  draw, eval, expand, parse, parse_expression, pretty, solve, steps, working, Error, Figure,
+  Format, Identity, AST,
+};
+
+// statements come back without their boxes, keeping the command that follows them
+#[test]
+fn parses() {
+  let statements = parse("y = 2x + 1; @draw\ny = 3;").unwrap();
+  assert_eq!(statements.len(), 2);
+  assert!(matches!(parse("y = 2x +"), Err(Error::Syntax(e)) if e.incomplete));
+}
+
+#[test]
+fn expands_and_evaluates() {
+  let ast = parse_expression("2(x + 1) + 3").unwrap();
+  assert_eq!(pretty(expand(ast.clone())), "3 + (1 + x) * 2");
+  assert_eq!(eval(&ast, 2f64), Ok(vec![9f64]));
+  assert_eq!(
+    eval(&parse("y = ±x;").unwrap()[0], 2f64),
+    Ok(vec![2f64, -2f64])
+  );
+  assert_eq!(
+    eval(&parse_expression("x + a").unwrap(), 0f64),
+    Err(Error::UnknownVariable("a".to_string()))
+  );
+  // a tree built by hand can hold an equation with no sides
+  let empty = AST::Identity(Identity {
+    identity: Vec::new(),
+  });
+  assert_eq!(eval(&empty, 0f64), Err(Error::NotAnEquation));
+}
+
+#[test]
+fn solves() {
+  let statement = &parse("2y = 3x + 6;").unwrap()[0];
+  let solved = solve(statement, "y").unwrap();
+  assert_eq!(eval(&solved, 4f64), Ok(vec![9f64]));
+  assert_eq!(
+    solve(statement, "z"),
+    Err(Error::MissingVariable("z".to_string()))
+  );
+  assert_eq!(
+    solve(&parse_expression("x + 1").unwrap(), "x"),
+    Err(Error::NotAnEquation)
+  );
+  assert_eq!(
+    solve(&parse("x^3 = 8;").unwrap()[0], "x"),
+    Err(Error::NotQuadratic("x".to_string()))
+  );
+  // y cancelling out leaves nothing to solve for, rather than an answer in x
+  assert_eq!(
+    solve(&parse("y - y = x;").unwrap()[0], "y"),
+    Err(Error::MissingVariable("y".to_string()))
+  );
+}
+
+// the working ends in the same solution, and steps refuse what solve refuses
+#[test]
+fn works() {
+  let statement = &parse("x^2 = 4;").unwrap()[0];
+  let working = working(statement, "x").unwrap();
+  assert_eq!(working.solution, solve(statement, "x").unwrap());
+  assert_eq!(working.roots(), Some(vec![2f64, -2f64]));
+  assert!(working.verify().unwrap().unwrap().iter().all(|c| c.sound()));
+  assert_eq!(
+    steps(statement, "x").unwrap().last().map(|s| &s.ast),
+    Some(&working.solution)
+  );
+  assert_eq!(
+    steps(&parse("y = 2;").unwrap()[0], "x"),
+    Err(Error::MissingVariable("x".to_string()))
+  );
+}
+
+#[test]
+fn draws() {
+  let file = std::env::temp_dir().join("graphing_calculator_api.svg");
+  let figure = Figure {
+    size: (200, 200),
+    format: Format::Svg,
+    ..Figure::default()
+  };
+  let equations = parse("y = x^2; y = 2x + 1;").unwrap();
+  assert_eq!(draw(&file, "api", &equations, &figure), Ok(()));
+  assert!(std::fs::read_to_string(&file).unwrap().contains("<svg"));
+  // a statement with no y is drawn as a vertical line
+  let vertical = parse("x = 3; y = 2x;").unwrap();
+  assert_eq!(draw(&file, "api", &vertical, &figure), Ok(()));
+  assert_eq!(
+    draw(&file, "api", &parse("y = a x;").unwrap(), &figure),
+    Err(Error::UnknownVariable("a".to_string()))
+  );
+  // the options of a statement style its curve and can name it in the legend
+  let styled = parse("y = x; @draw color=#00f style=dashed label=\"line\"").unwrap();
+  assert_eq!(draw(&file, "api", &styled, &figure), Ok(()));
+  assert!(std::fs::read_to_string(&file).unwrap().contains("line\n"));
+  assert!(draw(
+    &file,
+    "api",
+    &parse("y = x; @draw width=99").unwrap(),
+    &figure
+  )
+  .is_err());
+}
@@ -0,0 +1,40 @@
+// the exit codes of the binary, which scripts rely on to tell success from failure
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+// draw a .graph file written from source, keeping the svg out of the way
+fn draw(name: &str, source: &str) -> Output {
+  let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+  let graph = dir.join(format!("{}.graph", name));
+  fs::write(&graph, source).unwrap();
+  Command::new(env!("CARGO_BIN_EXE_graphing_calculator"))
+    .arg("draw")
+    .arg(&graph)
+    .arg(dir.join(format!("{}.svg", name)))
+    .output()
+    .unwrap()
+}
+
+#[test]
+fn draws() {
+  let output = draw("line", "y = 2x; @draw");
+  assert_eq!(output.status.code(), Some(0));
+}
+
+// a curve that cannot be drawn fails the whole drawing, saying why
+#[test]
+fn unknown_variable() {
+  let output = draw("unknown", "y = a x; @draw");
+  assert_eq!(output.status.code(), Some(1));
+  assert_eq!(
+    String::from_utf8_lossy(&output.stderr).trim(),
+    "Unknown variable a"
+  );
+}
+
+#[test]
+fn not_quadratic() {
+  let output = draw("cubic", "y^3 = x; @draw");
+  assert_eq!(output.status.code(), Some(1));
+}
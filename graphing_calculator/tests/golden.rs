@@ -0,0 +1,120 @@
+// every tests/*.graph is drawn by the cli and checked against what was blessed before:
+// the working printed to stdout against `<name>.out`, and the shape of the svg against `<name>.snap`.
+// run with BLESS=1 to write the current output as the expected output instead
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn blessing() -> bool {
+  std::env::var("BLESS").is_ok_and(|v| v.trim() == "1")
+}
+
+// the value of an attribute in a single svg element
+fn attribute<'a>(element: &'a str, name: &str) -> &'a str {
+  let start = match element.find(&format!(" {}=\"", name)) {
+    Some(i) => i + name.len() + 3,
+    None => return "",
+  };
+  let end = element[start..]
+    .find('"')
+    .map_or(element.len(), |i| start + i);
+  &element[start..end]
+}
+
+// the curves, points and labels of a drawing, leaving out the grid which every drawing shares
+fn structure(svg: &str) -> String {
+  let mut out = Vec::new();
+  let mut lines = svg.lines();
+  while let Some(line) = lines.next() {
+    // the axes and their ticks are black, every curve has a colour
+    if line.starts_with("<polyline") && attribute(line, "stroke") != "#000000" {
+      let points: Vec<&str> = attribute(line, "points").split_whitespace().collect();
+      out.push(format!(
+        "curve {} {} points from {} to {}",
+        attribute(line, "stroke"),
+        points.len(),
+        points.first().unwrap_or(&"-"),
+        points.last().unwrap_or(&"-")
+      ));
+    } else if line.starts_with("<circle") {
+      out.push(format!(
+        "point {},{}",
+        attribute(line, "cx"),
+        attribute(line, "cy")
+      ));
+    } else if line.starts_with("<text") {
+      out.push(format!("label {}", lines.next().unwrap_or_default().trim()));
+    }
+  }
+  out.join("\n") + "\n"
+}
+
+// whether the output matches what is expected, writing it instead when blessing
+fn check(expected: &Path, actual: &str, failures: &mut Vec<String>) {
+  if blessing() {
+    fs::write(expected, actual).unwrap();
+    return;
+  }
+  match fs::read_to_string(expected) {
+    Ok(text) if text.replace("\r\n", "\n") == actual => {}
+    Ok(text) => {
+      let line = text
+        .replace("\r\n", "\n")
+        .lines()
+        .zip(actual.lines())
+        .position(|(a, b)| a != b)
+        .unwrap_or(text.lines().count().min(actual.lines().count()));
+      failures.push(format!(
+        "{} differs from line {}",
+        expected.display(),
+        line + 1
+      ));
+    }
+    Err(_) => failures.push(format!("{} is missing", expected.display())),
+  }
+}
+
+#[test]
+fn golden() {
+  let mut graphs: Vec<PathBuf> = fs::read_dir("tests")
+    .unwrap()
+    .map(|entry| entry.unwrap().path())
+    .filter(|path| path.extension().is_some_and(|e| e == "graph"))
+    .collect();
+  graphs.sort();
+  assert!(!graphs.is_empty());
+
+  let mut failures = Vec::new();
+  for graph in graphs {
+    let name = graph.file_stem().unwrap().to_string_lossy().to_string();
+    let svg = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.svg", name));
+    let output = Command::new(env!("CARGO_BIN_EXE_graphing_calculator"))
+      .arg("draw")
+      .arg(&graph)
+      .arg(&svg)
+      .arg(&name)
+      .output()
+      .unwrap();
+    assert!(
+      output.status.success(),
+      "{}: {}",
+      name,
+      String::from_utf8_lossy(&output.stderr)
+    );
+    check(
+      &graph.with_extension("out"),
+      &String::from_utf8(output.stdout).unwrap(),
+      &mut failures,
+    );
+    check(
+      &graph.with_extension("snap"),
+      &structure(&fs::read_to_string(&svg).unwrap()),
+      &mut failures,
+    );
+  }
+  assert!(
+    failures.is_empty(),
+    "{}\nrun with BLESS=1 to accept the new output",
+    failures.join("\n")
+  );
+}